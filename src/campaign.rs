@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::event::ModifyKind;
+use notify::{EventKind, RecursiveMode, Watcher};
+use poise::serenity_prelude::{RoleId, User};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{error, info, warn};
+
+use crate::config::affinity::Affinity;
+use crate::config::backup::backup_players;
+use crate::config::difficulty::Difficulty;
+use crate::config::parser::{clean_string, get_tree, TreeStructure};
+use crate::config::players::{get_players, Player};
+use crate::config::stat::{get_stats, Stat};
+use crate::config::stat_rules::{RollRules, StatRules};
+use crate::config::{save_game_master_id, Config};
+use crate::history::RollHistory;
+use crate::Error;
+
+/// The configuration and list of stats of a campaign, that are replaced when its config files are reloaded
+#[derive(Debug)]
+pub struct Rules {
+    pub config: Config,                   // A global config
+    pub(crate) stats: Vec<Stat>,          // The stat tree that will be used to select a stat
+    pub(crate) affinities: Vec<Affinity>, // The available affinities groups
+    pub(crate) stat_rules: StatRules,     // The overrides of the config for some stats
+}
+
+impl Rules {
+    /// Parse the config files of the folder, and check that they are coherent with each other
+    fn from_folder(config_folder: &str) -> Result<(Self, HashMap<String, Player>), Error> {
+        info!("Loading config from {config_folder}");
+        let config = Config::from(&format!("{config_folder}/config.json"))?;
+        let stats = get_stats(config_folder)?;
+        let affinities = get_tree(&format!("{config_folder}/affinities.txt"))?;
+        let stat_rules = StatRules::from(&format!("{config_folder}/stat_rules.json"), &stats)?;
+        let players = get_players(&format!("{config_folder}/players"))?;
+
+        check_validity(&stats, &affinities, &players)?;
+        info!("Config files are correct");
+
+        Ok((
+            Rules {
+                config,
+                stats,
+                affinities,
+                stat_rules,
+            },
+            players,
+        ))
+    }
+
+    /// Get the rules of a roll for this stat, inherited from its stat families and the config
+    pub fn roll_rules(&self, stat: &Stat) -> RollRules<'_> {
+        self.stat_rules.for_stat(&self.stats, stat, &self.config)
+    }
+}
+
+/// Check that the config files of the folder are valid, without starting anything
+pub fn validate_config_files(config_folder: &str) -> Result<(), Error> {
+    Rules::from_folder(config_folder)?;
+    Ok(())
+}
+
+/// The players of a campaign indexed by their file.
+/// Updating a player only locks this player, while reloading the files locks every player
+type Players = RwLock<HashMap<String, Mutex<Player>>>;
+
+/// A campaign, with its own config files, players and roll history
+#[derive(Debug)]
+pub struct Campaign {
+    config_folder: String, // The folder containing the config files
+    rules: Arc<std::sync::RwLock<Arc<Rules>>>, // The current config and stats
+    players: Arc<Players>, // The players indexed by their file, each locked on its own
+    pub(crate) history: RollHistory, // The log of every roll made
+    next_difficulties: Mutex<HashMap<String, Difficulty>>, // The difficulty of the next roll of a player, by player file
+}
+
+impl Campaign {
+    pub fn from_config_files(config_folder: &str) -> Result<Self, Error> {
+        let (rules, players) = Rules::from_folder(config_folder)?;
+
+        backup_players(&format!("{config_folder}/players"), &rules.config.backups)?;
+        let history = RollHistory::from(&rules.config.history_file)?;
+
+        Ok(Campaign {
+            config_folder: config_folder.to_string(),
+            rules: Arc::new(std::sync::RwLock::new(Arc::new(rules))),
+            players: Arc::new(RwLock::new(lock_each(players))),
+            history,
+            next_difficulties: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Get the current configuration and stats
+    pub fn rules(&self) -> Arc<Rules> {
+        self.rules.read().unwrap().clone()
+    }
+
+    /// Parse the config files again, and use them only if they are valid.
+    /// Get the settings that changed but are only applied when the bot restarts
+    pub async fn reload(&self) -> Result<Vec<&'static str>, Error> {
+        reload_config_files(&self.config_folder, &self.rules, &self.players).await
+    }
+
+    /// Periodically back up the player files while the bot is running
+    pub fn spawn_backup_task(&self) {
+        let config = &self.rules().config;
+        let interval_minutes = config.backups.interval_minutes;
+        if interval_minutes == 0 {
+            info!("Periodic backups are disabled");
+            return;
+        }
+        let players_folder = format!("{}/players", self.config_folder);
+        let backup_config = config.backups.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_minutes * 60));
+            // The first tick completes immediately, and a backup was already made at startup
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = backup_players(&players_folder, &backup_config) {
+                    error!("Could not back up the player files: {e}");
+                }
+            }
+        });
+    }
+
+    /// Reload the config files whenever they are modified, if enabled in the config
+    pub fn spawn_config_watcher(&self) -> Result<(), Error> {
+        if !self.rules().config.watch_config_files {
+            return Ok(());
+        }
+        // The history and backups may be in the config folder, but they are not config files
+        let config = &self.rules().config;
+        let ignored_paths = [
+            Path::new(&config.history_file).to_path_buf(),
+            Path::new(&config.backups.folder).to_path_buf(),
+        ]
+        .iter()
+        .filter_map(|p| std::path::absolute(p).ok())
+        .collect::<Vec<PathBuf>>();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if should_reload(&event, &ignored_paths) => {
+                    let _ = sender.send(());
+                }
+                Ok(_) => (),
+                Err(e) => warn!("Error while watching the config files: {e}"),
+            })?;
+        watcher.watch(Path::new(&self.config_folder), RecursiveMode::Recursive)?;
+        info!("Watching {} for modifications", self.config_folder);
+
+        let config_folder = self.config_folder.clone();
+        let rules = self.rules.clone();
+        let players = self.players.clone();
+        tokio::spawn(async move {
+            // The watcher stops when dropped, so it is moved into the task
+            let _watcher = watcher;
+            while receiver.recv().await.is_some() {
+                // Saving a file often triggers several events, wait for all of them
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                while receiver.try_recv().is_ok() {}
+                info!("Config files were modified, reloading them");
+                if let Err(e) = reload_config_files(&config_folder, &rules, &players).await {
+                    error!("The config files were not reloaded: {e}");
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Get a copy of the player played by this discord user.
+    /// A player still identified by discord pseudo gets bound to the user id, so that renaming doesn't break it
+    pub async fn get_player(&self, user: &User) -> Option<Player> {
+        let players = self.players.read().await;
+        let copies = copy_each(&players).await;
+        let player_file = match copies.iter().find(|p| p.discord_id == Some(user.id)) {
+            Some(player) => player.path(),
+            None => copies.iter().find(|p| p.is_played_by(user))?.path(),
+        };
+        let mut player = players.get(player_file)?.lock().await;
+        if player.discord_id.is_none() {
+            info!("Binding player {} to discord user {}", player.name, user.id);
+            if let Err(e) = tokio::task::block_in_place(|| player.bind_to(user)) {
+                error!("Could not bind player {}: {e}", player.name);
+            }
+        }
+        Some(player.clone())
+    }
+
+    /// Is this discord user, who has the given roles on the server, a game master?
+    /// A game master still identified by discord pseudo gets their id saved in the config, so that renaming doesn't break it
+    pub async fn is_game_master(&self, user: &User, roles: &[RoleId]) -> bool {
+        let rules = self.rules();
+        if rules.config.is_game_master_by_pseudo(user) {
+            info!("Binding the game master to discord user {}", user.id);
+            let path = format!("{}/config.json", self.config_folder);
+            if let Err(e) = save_game_master_id(&path, user.id) {
+                error!("Could not bind the game master: {e}");
+            } else if let Err(e) = self.reload().await {
+                error!("Could not reload the config after binding the game master: {e}");
+            }
+        }
+        rules.config.is_game_master(user, roles)
+    }
+
+    /// Get a copy of every player
+    pub async fn get_players(&self) -> Vec<Player> {
+        copy_each(&*self.players.read().await).await
+    }
+
+    /// Get a copy of the player matching the input, either by file, discord id, discord name or player name
+    pub async fn find_player(&self, input: &str) -> Option<Player> {
+        let players = self.get_players().await;
+        if let Some(player) = players.iter().find(|p| p.path() == input) {
+            return Some(player.clone());
+        }
+        if let Ok(id) = input.trim().parse::<u64>() {
+            if let Some(player) = players
+                .iter()
+                .find(|p| p.discord_id.is_some_and(|i| i.get() == id))
+            {
+                return Some(player.clone());
+            }
+        }
+        let input = clean_string(input);
+        players
+            .into_iter()
+            .find(|p| clean_string(&p.name) == input || clean_string(&p.discord_name) == input)
+    }
+
+    /// Set the difficulty of the next roll of the player represented by this file
+    pub async fn set_next_difficulty(&self, player_file: &str, difficulty: Difficulty) {
+        self.next_difficulties
+            .lock()
+            .await
+            .insert(player_file.to_string(), difficulty);
+    }
+
+    /// Get the difficulty set for the next roll of the player represented by this file, only once
+    pub async fn take_next_difficulty(&self, player_file: &str) -> Option<Difficulty> {
+        self.next_difficulties.lock().await.remove(player_file)
+    }
+
+    /// Modify the player represented by this file.
+    /// Modifications of a player are applied one at a time, so that concurrent updates are never lost.
+    /// The update saves the player file, so it runs where blocking doesn't stall the other commands
+    pub async fn update_player<T>(
+        &self,
+        player_file: &str,
+        update: impl FnOnce(&mut Player) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let players = self.players.read().await;
+        let mut player = players
+            .get(player_file)
+            .ok_or(format!("No player found for {player_file}"))?
+            .lock()
+            .await;
+        tokio::task::block_in_place(|| update(&mut player))
+    }
+}
+
+// The bot saves player files by renaming a temporary file, these events should not trigger a reload
+fn should_reload(event: &notify::Event, ignored_paths: &[PathBuf]) -> bool {
+    let is_temporary_file = event
+        .paths
+        .iter()
+        .all(|p| p.extension().is_some_and(|e| e == "tmp"));
+    let is_ignored = event.paths.iter().all(|p| {
+        std::path::absolute(p).is_ok_and(|p| ignored_paths.iter().any(|i| p.starts_with(i)))
+    });
+    let is_rename = matches!(event.kind, EventKind::Modify(ModifyKind::Name(_)));
+    let is_access = matches!(event.kind, EventKind::Access(_));
+    !is_temporary_file && !is_ignored && !is_rename && !is_access
+}
+
+async fn reload_config_files(
+    config_folder: &str,
+    rules: &std::sync::RwLock<Arc<Rules>>,
+    players: &Players,
+) -> Result<Vec<&'static str>, Error> {
+    // Wait for the player updates in progress and prevent new ones while the files are read,
+    // so that no update is lost
+    let mut players = players.write().await;
+    let (new_rules, new_players) = Rules::from_folder(config_folder)?;
+    // The history, backups and watcher are set up once, when the bot starts
+    let old_rules = rules.read().unwrap().clone();
+    let old_config = &old_rules.config;
+    let mut needs_restart = vec![];
+    if new_rules.config.history_file != old_config.history_file {
+        needs_restart.push("history_file");
+    }
+    if new_rules.config.backups != old_config.backups {
+        needs_restart.push("backups");
+    }
+    if new_rules.config.watch_config_files != old_config.watch_config_files {
+        needs_restart.push("watch_config_files");
+    }
+    if !needs_restart.is_empty() {
+        warn!(
+            "Restart the bot to apply the changes of {}",
+            needs_restart.join(", ")
+        );
+    }
+    *rules.write().unwrap() = Arc::new(new_rules);
+    *players = lock_each(new_players);
+    info!("Config files reloaded");
+    Ok(needs_restart)
+}
+
+fn lock_each(players: HashMap<String, Player>) -> HashMap<String, Mutex<Player>> {
+    players
+        .into_iter()
+        .map(|(file, player)| (file, Mutex::new(player)))
+        .collect()
+}
+
+async fn copy_each(players: &HashMap<String, Mutex<Player>>) -> Vec<Player> {
+    let mut copies = Vec::with_capacity(players.len());
+    for player in players.values() {
+        copies.push(player.lock().await.clone());
+    }
+    copies
+}
+
+fn check_validity(
+    stats: &[Stat],
+    affinities: &[Affinity],
+    players: &HashMap<String, Player>,
+) -> Result<(), Error> {
+    info!("Checking config files coherence...");
+    // Create a flat vec of stats
+    let flat_stats: Vec<Stat> = stats.iter().flat_map(|s| s.flatten()).collect();
+
+    // Check validity of affinities
+    let flat_affinities: Vec<Affinity> = affinities.iter().flat_map(|a| a.flatten()).collect();
+    for affinity in flat_affinities {
+        if !flat_stats.iter().any(|s| s.id == affinity.id) {
+            return Err(format!(
+                "Affinity stat {:?} is not in stat file",
+                affinity.display_name,
+            )
+            .into());
+        }
+    }
+
+    // Check validity of each player
+    for player in players.values() {
+        let file_path = player.path();
+        for stat in player.stats.keys() {
+            if !flat_stats.iter().any(|s| &s.display_name == stat) {
+                return Err(format!(
+                    "Stat {:?} from file {} is not in stat file",
+                    stat, file_path
+                )
+                .into());
+            }
+        }
+        for stat in &flat_stats {
+            if !player.stats.iter().any(|(s, _)| s == &stat.display_name) {
+                return Err(
+                    format!("Stat {:?} is not in file {}", stat.display_name, file_path).into(),
+                );
+            }
+        }
+        for major_affinity in &player.affinities.major {
+            if !affinities.iter().any(|a| &a.display_name == major_affinity) {
+                return Err(format!(
+                    "Major affinity {:?} from file {} is not in stat file",
+                    major_affinity, file_path
+                )
+                .into());
+            }
+        }
+        for minor_affinity in &player.affinities.minor {
+            if !affinities.iter().any(|a| &a.display_name == minor_affinity) {
+                return Err(format!(
+                    "Minor affinity {:?} from file {} is not in stat file",
+                    minor_affinity, file_path
+                )
+                .into());
+            }
+        }
+        for talent in &player.talents {
+            if !flat_stats.iter().any(|s| &s.display_name == talent) {
+                return Err(format!(
+                    "Talent {:?} from file {} is not in stat file",
+                    talent, file_path
+                )
+                .into());
+            }
+        }
+        if let Some(id) = player.discord_id {
+            if let Some(other) = players
+                .values()
+                .find(|p| p.discord_id == Some(id) && p.path() != file_path)
+            {
+                return Err(format!(
+                    "Discord user {id} is bound to both {} and {}",
+                    file_path,
+                    other.path()
+                )
+                .into());
+            }
+        }
+    }
+
+    Ok(())
+}
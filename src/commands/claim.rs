@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use poise::serenity_prelude::{
+    ButtonStyle, CreateActionRow, CreateInteractionResponse, CreateInteractionResponseMessage,
+    Mentionable,
+};
+use poise::CreateReply;
+use tracing::info;
+
+use crate::{Context, Error};
+
+use super::utils::{
+    autocomplete_player, button, clicked_by_game_master, command_button_collector,
+    command_button_id, get_campaign,
+};
+
+/// Ask the game master to play a player. It stays yours even if you change your pseudo.
+#[poise::command(slash_command)]
+pub async fn claim(
+    ctx: Context<'_>,
+    #[description = "The player you want to play"]
+    #[autocomplete = "autocomplete_player"]
+    player: String,
+) -> Result<(), Error> {
+    let author = ctx.author();
+    let campaign = get_campaign(&ctx).await?;
+    let Some(player) = campaign.find_player(&player).await else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!("Unknown player {player:?}.")),
+        )
+        .await?;
+        return Err(format!("Unknown player {player:?}").into());
+    };
+    if let Some(current) = campaign.get_player(author).await {
+        let content = match current.path() == player.path() {
+            true => format!("You already play {}.", player.name),
+            false => format!(
+                "You already play {}, you can't play {} too.",
+                current.name, player.name
+            ),
+        };
+        ctx.send(CreateReply::default().ephemeral(true).content(content))
+            .await?;
+        return Ok(());
+    }
+
+    let approve_id = command_button_id(&ctx, "approve");
+    let reject_id = command_button_id(&ctx, "reject");
+    let mut content = format!("{} wants to play **{}**.", author.mention(), player.name);
+    if player.discord_id.is_some() {
+        content += &format!(
+            " This player is currently played by {}.",
+            player.discord_name
+        );
+    }
+    content += "\nThe game master should approve or reject this request.";
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(vec![CreateActionRow::Buttons(vec![
+                button(&approve_id, "Approve", ButtonStyle::Success),
+                button(&reject_id, "Reject", ButtonStyle::Danger),
+            ])]),
+    )
+    .await?;
+    info!("{} asked to play {}", author.name, player.name);
+
+    // Anyone can click the buttons, but only the answer of the game master is taken into account
+    while let Some(interaction) = command_button_collector(&ctx)
+        .timeout(Duration::from_secs(600))
+        .await
+    {
+        if !clicked_by_game_master(campaign, &interaction).await {
+            interaction
+                .create_response(
+                    ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::default()
+                            .ephemeral(true)
+                            .content("Only a game master can answer this request."),
+                    ),
+                )
+                .await?;
+            continue;
+        }
+        let content = if interaction.data.custom_id == approve_id {
+            campaign
+                .update_player(player.path(), |p| p.bind_to(author))
+                .await?;
+            info!("{} now plays {}", author.name, player.name);
+            format!("{} now plays **{}**.", author.mention(), player.name)
+        } else {
+            info!("{} was not allowed to play {}", author.name, player.name);
+            format!(
+                "The game master didn't allow {} to play **{}**.",
+                author.mention(),
+                player.name
+            )
+        };
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::default()
+                        .content(content)
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content("The game master didn't answer your request in time, try again later."),
+    )
+    .await?;
+    Ok(())
+}
@@ -1,36 +1,74 @@
 use poise::serenity_prelude::CreateEmbed;
 use poise::CreateReply;
-use rand::{rngs::StdRng, Rng};
 use tracing::info;
 
-use crate::{config::players::Player, Context, Error};
+use crate::dice::roll_dice;
+use crate::{Context, Error};
 
-/// Roll a dice with a given number of faces.
+use super::utils::{check_secret_argument, get_campaign, send_result};
+
+static MAX_FIELDS: usize = 25;
+static MAX_FIELDS_LENGTH: usize = 5000;
+
+/// Roll dice, for example 3d6+2, 2d20kh1, 4d6dl1, d6!, 2d10r1 or 4dF.
 #[poise::command(slash_command)]
 pub async fn dice(
     ctx: Context<'_>,
-    #[description = "Number of faces of the dice"] faces: u32,
+    #[description = "The dice to roll, like 3d6+2, or only a number of faces"] dice: String,
+    #[description = "Only show the result to the game master"] secret: Option<bool>,
 ) -> Result<(), Error> {
-    info!("Rolling a dice with {faces} faces");
+    let secret = check_secret_argument(&ctx, secret).await?;
+    // A number alone is the number of faces of a single dice
+    let expression = match dice.trim().parse::<u32>() {
+        Ok(faces) => format!("d{faces}"),
+        Err(_) => dice.trim().to_owned(),
+    };
+    info!("Rolling {expression}");
+
+    let roll = match ctx.data().rng.with(|rng| roll_dice(&expression, rng)) {
+        Ok(roll) => roll,
+        Err(e) => {
+            ctx.send(
+                CreateReply::default()
+                    .ephemeral(true)
+                    .content(format!("Invalid dice {expression:?}: {e}.")),
+            )
+            .await?;
+            return Err(e);
+        }
+    };
+    info!("Rolled {} for {expression}", roll.total);
 
     let discord_name = &ctx.author().name;
-    let player = ctx.data().players.get(discord_name);
-    let player_name = match player {
-        Some(p) => Player::from(p)?.name,
+    let player_name = match get_campaign(&ctx).await?.get_player(ctx.author()).await {
+        Some(p) => p.name,
         None => discord_name.to_owned(),
     };
 
-    let mut rng: StdRng = rand::SeedableRng::from_entropy();
-    let roll = rng.gen_range(1..(faces + 1));
-
-    info!("Rolled {roll}/{faces}");
-
-    ctx.send(
-        CreateReply::default().embed(
-            CreateEmbed::default()
-                .title(format!("**{player_name}**"))
-                .description(format!("d{faces}: **{roll}**")),
-        ),
+    // An embed can hold 25 fields and 6000 characters, the groups that don't fit are left out
+    let mut fields = vec![];
+    let mut length = 0;
+    for group in &roll.groups {
+        let breakdown = group.breakdown();
+        length += group.notation.len() + breakdown.len();
+        if fields.len() == MAX_FIELDS || length > MAX_FIELDS_LENGTH {
+            fields.pop();
+            fields.push((
+                "…".to_owned(),
+                "Too many dice to show them all".to_owned(),
+                false,
+            ));
+            break;
+        }
+        fields.push((group.notation.clone(), breakdown, true));
+    }
+    send_result(
+        &ctx,
+        CreateEmbed::default()
+            .title(format!("**{player_name}**"))
+            .description(format!("{expression}: **{}**", roll.total))
+            .fields(fields),
+        secret,
     )
     .await?;
 
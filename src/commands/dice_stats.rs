@@ -0,0 +1,86 @@
+use poise::serenity_prelude::CreateEmbed;
+use poise::CreateReply;
+use tracing::info;
+
+use crate::{Context, Error};
+
+use super::roll::find_stat_argument;
+use super::utils::{autocomplete_stat, game_master_only, get_campaign};
+
+static BUCKET_SIZE: usize = 10;
+static BAR_WIDTH: usize = 20;
+
+/// Draw a histogram of the rolls, grouped by tens
+fn format_histogram(rolls: &[i32]) -> String {
+    let mut buckets = [0usize; 100 / BUCKET_SIZE];
+    for roll in rolls {
+        buckets[(*roll as usize - 1) / BUCKET_SIZE] += 1;
+    }
+    let highest = buckets.iter().max().copied().unwrap_or(0).max(1);
+    let lines: Vec<String> = buckets
+        .iter()
+        .enumerate()
+        .map(|(i, count)| {
+            format!(
+                "{:>3}-{:<3} {:<width$} {:>5.1}%",
+                i * BUCKET_SIZE + 1,
+                (i + 1) * BUCKET_SIZE,
+                "█".repeat(count * BAR_WIDTH / highest),
+                *count as f64 * 100.0 / rolls.len() as f64,
+                width = BAR_WIDTH
+            )
+        })
+        .collect();
+    format!("```\n{}\n```", lines.join("\n"))
+}
+
+/// Simulate many rolls and show their distribution. Only game masters can use this command.
+#[poise::command(slash_command, rename = "dice-stats", check = "game_master_only")]
+pub async fn dice_stats(
+    ctx: Context<'_>,
+    #[description = "Number of rolls to simulate, 10000 by default"]
+    #[min = 100]
+    #[max = 1000000]
+    rolls: Option<u32>,
+    #[description = "Use the statistic law of this stat instead of the one of the config"]
+    #[autocomplete = "autocomplete_stat"]
+    stat: Option<String>,
+) -> Result<(), Error> {
+    let campaign = get_campaign(&ctx).await?;
+    let rules = campaign.rules();
+    let law = match stat {
+        Some(input) => {
+            rules
+                .roll_rules(&find_stat_argument(&ctx, &input).await?)
+                .statistic_law
+        }
+        None => &rules.config.roll_command_statistic_law,
+    };
+    let count = rolls.unwrap_or(10000);
+    info!("Simulating {count} rolls with the {law} law");
+    // Up to a million rolls are simulated on a thread of their own, with a generator of their own
+    let mut rng = ctx.data().rng.fork();
+    let simulated_law = law.clone();
+    let mut rolls = tokio::task::spawn_blocking(move || {
+        (0..count)
+            .map(|_| simulated_law.roll(&mut rng))
+            .collect::<Result<Vec<i32>, Error>>()
+    })
+    .await??;
+    rolls.sort_unstable();
+    let mean = rolls.iter().map(|r| *r as f64).sum::<f64>() / rolls.len() as f64;
+
+    let embed = CreateEmbed::default()
+        .title(format!("{count} simulated rolls"))
+        .description(format_histogram(&rolls))
+        .fields([
+            ("Law", law.to_string(), false),
+            ("Mean", format!("{mean:.1}"), true),
+            ("Median", rolls[rolls.len() / 2].to_string(), true),
+            ("Min", rolls[0].to_string(), true),
+            ("Max", rolls[rolls.len() - 1].to_string(), true),
+        ]);
+    ctx.send(CreateReply::default().ephemeral(true).embed(embed))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use poise::CreateReply;
+use tracing::info;
+
+use crate::config::difficulty::Difficulty;
+use crate::{Context, Error};
+
+use super::utils::{autocomplete_player, game_master_only, get_campaign};
+
+/// Set the difficulty of the next roll of a player. Only game masters can use this command.
+#[poise::command(slash_command, check = "game_master_only")]
+pub async fn difficulty(
+    ctx: Context<'_>,
+    #[description = "The player whose next roll will have this difficulty"]
+    #[autocomplete = "autocomplete_player"]
+    player: String,
+    #[description = "The difficulty of the next roll"] difficulty: Difficulty,
+) -> Result<(), Error> {
+    let campaign = get_campaign(&ctx).await?;
+    let Some(player) = campaign.find_player(&player).await else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!("Unknown player {player:?}.")),
+        )
+        .await?;
+        return Err(format!("Unknown player {player:?}").into());
+    };
+    campaign
+        .set_next_difficulty(player.path(), difficulty)
+        .await;
+    info!("The next roll of {} is {difficulty}", player.name);
+    ctx.send(CreateReply::default().ephemeral(true).content(format!(
+        "The next roll of {} will be {difficulty}.",
+        player.name
+    )))
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use poise::CreateReply;
+use tracing::info;
+
+use crate::config::difficulty::Difficulty;
+use crate::{Context, Error};
+
+use super::roll::{find_stat_argument, roll_with_player, Edge, RollOptions};
+use super::utils::{
+    autocomplete_player, autocomplete_stat, check_secret_argument, display_result,
+    game_master_only, get_campaign,
+};
+
+/// Roll a dice for a player as the game master. The player's experience will be updated.
+#[allow(clippy::too_many_arguments)]
+#[poise::command(slash_command, check = "game_master_only")]
+pub async fn gmroll(
+    ctx: Context<'_>,
+    #[description = "The player to roll for"]
+    #[autocomplete = "autocomplete_player"]
+    player: String,
+    #[description = "The stat or stat family to roll for, skips the stat selection"]
+    #[autocomplete = "autocomplete_stat"]
+    stat: Option<String>,
+    #[description = "A temporary bonus (or malus if negative) for this roll only"]
+    #[min = -100]
+    #[max = 100]
+    modifier: Option<i32>,
+    #[description = "Roll several dice and keep the best (advantage) or the worst (disadvantage)"]
+    edge: Option<Edge>,
+    #[description = "The number of dice rolled with an advantage or disadvantage, 2 by default"]
+    #[min = 2]
+    #[max = 10]
+    edge_dice: Option<u32>,
+    #[description = "The difficulty of the roll, normal by default"] difficulty: Option<Difficulty>,
+    #[description = "Only show the result to the game master"] secret: Option<bool>,
+) -> Result<(), Error> {
+    let discord_name = &ctx.author().name;
+
+    let player = match get_campaign(&ctx).await?.find_player(&player).await {
+        Some(p) => p,
+        None => {
+            ctx.send(
+                CreateReply::default()
+                    .ephemeral(true)
+                    .content(format!("Unknown player {player:?}.")),
+            )
+            .await?;
+            return Err(format!("Unknown player {player:?}").into());
+        }
+    };
+    let stat = match stat {
+        Some(input) => Some(find_stat_argument(&ctx, &input).await?),
+        None => None,
+    };
+
+    let options = RollOptions {
+        secret: check_secret_argument(&ctx, secret).await?,
+        situational_modifier: modifier.unwrap_or(0),
+        edge: RollOptions::edge_from(edge, edge_dice),
+        difficulty,
+    };
+
+    info!("Game master {discord_name} is rolling for {}", player.name);
+    let (mut roll_result, interaction) =
+        roll_with_player(&ctx, player.path(), stat, &options).await?;
+    roll_result.rolled_by = Some(discord_name.to_string());
+    display_result(&ctx, interaction, &roll_result).await?;
+    Ok(())
+}
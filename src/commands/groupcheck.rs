@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+use poise::serenity_prelude::{
+    ButtonStyle, ComponentInteraction, CreateActionRow, CreateEmbed, CreateInteractionResponse,
+    CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+};
+use poise::{ChoiceParameter, CreateReply};
+use tracing::info;
+
+use crate::config::difficulty::Difficulty;
+use crate::config::stat::Stat;
+use crate::{Context, Error};
+
+use super::roll::{find_stat_argument, roll_for_player, RollOptions, RollResult};
+use super::utils::{
+    autocomplete_stat, button, clicked_by_game_master, command_button_collector, command_button_id,
+    format_roll, game_master_only, get_campaign, record_roll,
+};
+
+/// How many players of the party must succeed for the group check to succeed
+#[derive(Debug, Clone, Copy, ChoiceParameter)]
+pub enum GroupRule {
+    #[name = "Everyone must succeed"]
+    All,
+    #[name = "A majority must succeed"]
+    Majority,
+    #[name = "At least one must succeed"]
+    AtLeastOne,
+}
+
+impl GroupRule {
+    fn is_met(&self, successes: usize, total: usize) -> bool {
+        match self {
+            GroupRule::All => total > 0 && successes == total,
+            GroupRule::Majority => successes * 2 > total,
+            GroupRule::AtLeastOne => successes > 0,
+        }
+    }
+}
+
+/// Build the table of the results of the players who rolled so far
+fn group_check_embed(
+    stat: &Stat,
+    rule: GroupRule,
+    results: &[RollResult],
+    finished: bool,
+) -> CreateEmbed {
+    let mut description = match finished {
+        true => "The group check is over.\n".to_owned(),
+        false => "Click on **Roll** to take part in the group check.\n".to_owned(),
+    };
+    for roll_result in results {
+        description += &format!(
+            "\n**{}**: {}",
+            roll_result.player_name,
+            format_roll(roll_result)
+        );
+    }
+    let successes = results
+        .iter()
+        .filter(|r| r.outcome.is_some_and(|o| o.is_success()))
+        .count();
+    let title = match (finished, rule.is_met(successes, results.len())) {
+        (false, _) => format!("GROUP CHECK / {}", stat.display_name),
+        (true, true) => format!("GROUP SUCCESS / {}", stat.display_name),
+        (true, false) => format!("GROUP FAILURE / {}", stat.display_name),
+    };
+    CreateEmbed::default()
+        .title(title)
+        .description(description)
+        .field(
+            "Result",
+            format!("{successes}/{} succeeded ({})", results.len(), rule.name()),
+            false,
+        )
+}
+
+/// Reply only to the player who clicked the button
+async fn reply_to_player(
+    ctx: &Context<'_>,
+    interaction: &ComponentInteraction,
+    content: &str,
+) -> Result<(), Error> {
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::default()
+                    .ephemeral(true)
+                    .content(content),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Ask every player to roll the same stat. Only game masters can use this command.
+#[poise::command(slash_command, check = "game_master_only")]
+pub async fn groupcheck(
+    ctx: Context<'_>,
+    #[description = "The stat every player rolls for"]
+    #[autocomplete = "autocomplete_stat"]
+    stat: String,
+    #[description = "How many players must succeed, a majority by default"] rule: Option<GroupRule>,
+    #[description = "The difficulty of the roll, normal by default"] difficulty: Option<Difficulty>,
+) -> Result<(), Error> {
+    let campaign = get_campaign(&ctx).await?;
+    let rule = rule.unwrap_or(GroupRule::Majority);
+    let options = RollOptions {
+        difficulty,
+        ..Default::default()
+    };
+    let stat = find_stat_argument(&ctx, &stat).await?;
+    if !stat.sub_stats.is_empty() {
+        ctx.send(CreateReply::default().ephemeral(true).content(format!(
+            "{} is a stat family, choose one of its stats.",
+            stat.display_name
+        )))
+        .await?;
+        return Err(format!("{} is a stat family", stat.display_name).into());
+    }
+
+    let roll_id = command_button_id(&ctx, "roll");
+    let close_id = command_button_id(&ctx, "close");
+    let buttons = vec![CreateActionRow::Buttons(vec![
+        button(&roll_id, "Roll", ButtonStyle::Primary),
+        button(&close_id, "Close", ButtonStyle::Danger),
+    ])];
+    let mut results: Vec<RollResult> = vec![];
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .embed(group_check_embed(&stat, rule, &results, false))
+                .components(buttons.clone()),
+        )
+        .await?;
+    info!("Group check started for {}", stat.display_name);
+
+    while let Some(interaction) = command_button_collector(&ctx)
+        .timeout(Duration::from_secs(600))
+        .await
+    {
+        if interaction.data.custom_id == close_id {
+            if !clicked_by_game_master(campaign, &interaction).await {
+                reply_to_player(
+                    &ctx,
+                    &interaction,
+                    "Only a game master can close the group check.",
+                )
+                .await?;
+                continue;
+            }
+            interaction
+                .create_response(ctx, CreateInteractionResponse::Acknowledge)
+                .await?;
+            break;
+        }
+
+        let Some(player) = campaign.get_player(&interaction.user).await else {
+            reply_to_player(&ctx, &interaction, "You don't have player data yet.").await?;
+            continue;
+        };
+        if results
+            .iter()
+            .any(|r| r.player_file.as_deref() == Some(player.path()))
+        {
+            reply_to_player(
+                &ctx,
+                &interaction,
+                "You already rolled for this group check.",
+            )
+            .await?;
+            continue;
+        }
+        let roll_result = roll_for_player(&ctx, player.path(), &stat, &options).await?;
+        info!("{} rolled for the group check", player.name);
+        // The message holds the rolls of every player, it is not marked as cancelled when one of them is undone
+        record_roll(&ctx, &roll_result, None).await?;
+        let level_up = match (roll_result.mastery, roll_result.new_mastery) {
+            (Some(old), Some(new)) if new > old => Some(new),
+            _ => None,
+        };
+        results.push(roll_result);
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::default()
+                        .embed(group_check_embed(&stat, rule, &results, false))
+                        .components(buttons.clone()),
+                ),
+            )
+            .await?;
+        // Only the player who leveled up is told about it, to not flood the channel
+        if let Some(mastery) = level_up {
+            interaction
+                .create_followup(
+                    ctx,
+                    CreateInteractionResponseFollowup::default()
+                        .ephemeral(true)
+                        .content(format!("🎉 Leveled up {} to {mastery}!", stat.display_name)),
+                )
+                .await?;
+        }
+    }
+
+    info!("Group check for {} is over", stat.display_name);
+    reply
+        .edit(
+            ctx,
+            CreateReply::default()
+                .embed(group_check_embed(&stat, rule, &results, true))
+                .components(vec![]),
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use poise::CreateReply;
+
+use crate::history::{HistoryFilter, RollRecord};
+use crate::{Context, Error};
+
+use super::utils::{
+    autocomplete_player, autocomplete_stat, get_campaign, is_game_master, send_pages,
+};
+
+static ROLLS_PER_PAGE: usize = 10;
+
+fn format_record(record: &RollRecord) -> String {
+    let mut line = format!(
+        "`#{}` {} **{}**",
+        record.id,
+        record.timestamp.format("%Y-%m-%d %H:%M"),
+        record.player_name
+    );
+    if let Some(stat) = &record.stat {
+        line += &format!(" / *{stat}*");
+    }
+    line += &format!(": {}", record.roll);
+    if record.rolls.len() > 1 {
+        let rolls: Vec<String> = record.rolls.iter().map(|r| r.to_string()).collect();
+        line += &format!(" (kept out of {})", rolls.join(", "));
+    }
+    if let Some(mastery) = record.mastery {
+        line += &format!(" / {mastery}");
+        match record.modifier {
+            Some(modif) if modif < 0 => line += &format!(" - {}", modif.abs()),
+            Some(modif) if modif > 0 => line += &format!(" + {}", modif.abs()),
+            _ => (),
+        }
+        if let Some(modif) = record.situational_modifier.filter(|m| *m != 0) {
+            line += &format!(" ({modif:+} situational)");
+        }
+        if let Some(tier) = record.difficulty_tier {
+            line += &format!(" ({tier})");
+        }
+        // The difficulty of the stat is subtracted from the threshold
+        if let Some(difficulty) = record.difficulty.filter(|d| *d != 0) {
+            line += &format!(" ({:+} stat difficulty)", -difficulty);
+        }
+    }
+    match (record.outcome, record.successful) {
+        (Some(outcome), _) => line += &format!(" → {} ({:+})", outcome.title(), outcome.margin()),
+        (None, Some(true)) => line += " → SUCCESS",
+        (None, Some(false)) => line += " → FAILURE",
+        (None, None) => (),
+    }
+    if let Some(experience) = record.experience_earned {
+        line += &format!(" (+{experience} xp)");
+    }
+    if record.secret {
+        line += " 🤫";
+    }
+    if record.voided {
+        line = format!("~~{line}~~ (cancelled)");
+    }
+    line
+}
+
+/// Display the last rolls, optionally filtered by player, stat or session.
+#[poise::command(slash_command)]
+pub async fn history(
+    ctx: Context<'_>,
+    #[description = "Number of rolls to display, 50 by default"]
+    #[min = 1]
+    #[max = 500]
+    count: Option<u32>,
+    #[description = "Only display the rolls of this player"]
+    #[autocomplete = "autocomplete_player"]
+    player: Option<String>,
+    #[description = "Only display the rolls of this stat"]
+    #[autocomplete = "autocomplete_stat"]
+    stat: Option<String>,
+    #[description = "Only display the rolls of this day (YYYY-MM-DD)"] session: Option<String>,
+) -> Result<(), Error> {
+    let campaign = get_campaign(&ctx).await?;
+    // The rolls are matched by player file, and the older ones without file by name
+    let (player, player_file) = match player {
+        Some(input) => match campaign.find_player(&input).await {
+            Some(p) => (Some(p.name.clone()), Some(p.path().to_string())),
+            None => (Some(input), None),
+        },
+        None => (None, None),
+    };
+    let filter = HistoryFilter {
+        player,
+        player_file,
+        stat,
+        session,
+        include_secret: is_game_master(&ctx).await,
+    };
+    let records = campaign
+        .history
+        .last(count.unwrap_or(50) as usize, &filter)
+        .await?;
+
+    if records.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("No roll found."),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // Display the most recent rolls first
+    let lines: Vec<String> = records.iter().rev().map(format_record).collect();
+    let pages: Vec<String> = lines
+        .chunks(ROLLS_PER_PAGE)
+        .map(|chunk| chunk.join("\n"))
+        .collect();
+    send_pages(&ctx, "Roll history", &pages).await
+}
@@ -1,6 +1,15 @@
+pub mod claim;
 pub mod dice;
+pub mod dice_stats;
+pub mod difficulty;
+pub mod gmroll;
+pub mod groupcheck;
+pub mod history;
+pub mod opposed;
 pub mod ping;
 pub mod register;
+pub mod reload;
 pub mod roll;
 pub mod summary;
+pub mod undo;
 pub mod utils;
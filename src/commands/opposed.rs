@@ -0,0 +1,162 @@
+use std::cmp::Ordering;
+use std::time::Duration;
+
+use poise::serenity_prelude::{
+    ButtonStyle, CreateActionRow, CreateEmbed, CreateInteractionResponse,
+    CreateInteractionResponseMessage, Mentionable,
+};
+use poise::CreateReply;
+use tracing::info;
+
+use crate::config::stat::Stat;
+use crate::{Context, Error};
+
+use super::roll::{find_stat_argument, roll_for_player, select_stat, RollOptions, RollResult};
+use super::utils::{
+    announce_level_up, autocomplete_player, autocomplete_stat, button, command_button_collector,
+    command_button_id, finish_interaction, format_roll, get_campaign, record_roll,
+};
+
+/// Describe the roll of one side of the contest
+fn format_side(stat: &Stat, roll_result: &RollResult) -> String {
+    format!("*{}*: {}", stat.display_name, format_roll(roll_result))
+}
+
+/// Challenge another player, you both roll for a stat and the best result wins.
+#[poise::command(slash_command)]
+pub async fn opposed(
+    ctx: Context<'_>,
+    #[description = "The player you challenge"]
+    #[autocomplete = "autocomplete_player"]
+    target: String,
+    #[description = "The stat or stat family you roll for, skips the stat selection"]
+    #[autocomplete = "autocomplete_stat"]
+    stat: Option<String>,
+) -> Result<(), Error> {
+    let campaign = get_campaign(&ctx).await?;
+    let refuse = |content: String| async move {
+        ctx.send(CreateReply::default().ephemeral(true).content(&content))
+            .await?;
+        Err::<(), Error>(content.into())
+    };
+    let Some(initiator) = campaign.get_player(ctx.author()).await else {
+        return refuse("You don't have player data yet, you can't challenge anyone.".into()).await;
+    };
+    let Some(target) = campaign.find_player(&target).await else {
+        return refuse(format!("Unknown player {target:?}.")).await;
+    };
+    if target.path() == initiator.path() {
+        return refuse("You can't challenge yourself.".into()).await;
+    }
+    // The id is needed to know who can answer the challenge
+    let Some(target_id) = target.discord_id else {
+        return refuse(format!(
+            "{} has never used the bot, they can't answer a challenge yet.",
+            target.name
+        ))
+        .await;
+    };
+    let stat = match stat {
+        Some(input) => Some(find_stat_argument(&ctx, &input).await?),
+        None => None,
+    };
+
+    let (initiator_stat, interaction) = select_stat(&ctx, None, stat, ctx.author().id).await?;
+    if let Some(interaction) = interaction {
+        let content = format!("You chose {}.", initiator_stat.display_name);
+        finish_interaction(&ctx, interaction, &content).await?;
+    }
+
+    info!(
+        "{} challenges {} with {}",
+        initiator.name, target.name, initiator_stat.display_name
+    );
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content(format!(
+                    "{}, **{}** challenges you with *{}*!",
+                    target_id.mention(),
+                    initiator.name,
+                    initiator_stat.display_name
+                ))
+                .components(vec![CreateActionRow::Buttons(vec![button(
+                    &command_button_id(&ctx, "answer"),
+                    "Choose your stat",
+                    ButtonStyle::Primary,
+                )])]),
+        )
+        .await?;
+    let answer = match command_button_collector(&ctx)
+        .author_id(target_id)
+        .timeout(Duration::from_secs(60))
+        .await
+    {
+        Some(answer) => answer,
+        None => {
+            reply
+                .edit(
+                    ctx,
+                    CreateReply::default()
+                        .content(format!(
+                            "{} didn't answer the challenge of {} in time.",
+                            target.name, initiator.name
+                        ))
+                        .components(vec![]),
+                )
+                .await?;
+            return Err(format!("{} didn't answer the challenge", target.name).into());
+        }
+    };
+    let (target_stat, interaction) = select_stat(&ctx, Some(answer), None, target_id).await?;
+    let interaction = interaction.ok_or("The target should have chosen a stat")?;
+
+    // Both sides roll and earn experience as usual
+    let options = RollOptions::default();
+    let initiator_result =
+        roll_for_player(&ctx, initiator.path(), &initiator_stat, &options).await?;
+    let target_result = roll_for_player(&ctx, target.path(), &target_stat, &options).await?;
+    let title = match (&initiator_result.outcome, &target_result.outcome) {
+        (Some(i), Some(t)) => match i.opposed_cmp(t) {
+            Ordering::Greater => format!("{} WINS", initiator.name.to_uppercase()),
+            Ordering::Less => format!("{} WINS", target.name.to_uppercase()),
+            Ordering::Equal => "DRAW".to_owned(),
+        },
+        _ => return Err("Opposed rolls should have an outcome".into()),
+    };
+    info!("Opposed roll result: {title}");
+
+    let embed = CreateEmbed::default()
+        .title(title)
+        .description(format!("**{}** vs **{}**", initiator.name, target.name))
+        .fields([
+            (
+                initiator.name.as_str(),
+                format_side(&initiator_stat, &initiator_result),
+                false,
+            ),
+            (
+                target.name.as_str(),
+                format_side(&target_stat, &target_result),
+                false,
+            ),
+        ]);
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::default()
+                    .content("")
+                    .embed(embed)
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+
+    // The message holds both rolls, it is not marked as cancelled when one of them is undone
+    for roll_result in [&initiator_result, &target_result] {
+        record_roll(&ctx, roll_result, None).await?;
+        announce_level_up(&ctx, roll_result).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use poise::CreateReply;
+use tracing::info;
+
+use crate::{Context, Error};
+
+use super::utils::{game_master_only, get_campaign};
+
+/// Reload the config files without restarting the bot. Only game masters can use this command.
+#[poise::command(slash_command, check = "game_master_only")]
+pub async fn reload(ctx: Context<'_>) -> Result<(), Error> {
+    info!("Reloading config files");
+    let content = match get_campaign(&ctx).await?.reload().await {
+        Ok(needs_restart) if needs_restart.is_empty() => "Config files reloaded.".to_owned(),
+        Ok(needs_restart) => format!(
+            "Config files reloaded. Restart the bot to apply the changes of {}.",
+            needs_restart.join(", ")
+        ),
+        Err(e) => format!("The config files are invalid and were not reloaded:\n{e}"),
+    };
+    ctx.send(CreateReply::default().ephemeral(true).content(content))
+        .await?;
+    Ok(())
+}
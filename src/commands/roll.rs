@@ -1,22 +1,24 @@
 use std::fmt::Display;
 
 use async_recursion::async_recursion;
-use rand::rngs::StdRng;
 use rand::Rng;
-use rand_distr::{Distribution, Normal};
 
-use poise::serenity_prelude::ComponentInteraction;
+use poise::serenity_prelude::{ComponentInteraction, UserId};
+use poise::{ChoiceParameter, CreateReply};
 use tracing::{error, info, warn};
 
 use crate::{
     commands::utils::display_result,
     config::players::Player,
-    config::{affinity::Affinity, stat::Stat, StatisticLaw},
-    Config,
+    config::{
+        affinity::Affinity, difficulty::Difficulty, outcome::Outcome, stat::Stat,
+        stat_rules::RollRules, Config,
+    },
 };
 
 use super::utils::{
-    finish_interaction, get_mastery, send_choose_stats_message, send_yes_no_message,
+    autocomplete_stat, check_secret_argument, finish_interaction, get_campaign, get_current_level,
+    get_mastery, is_game_master, send_choose_stats_message, send_yes_no_message,
 };
 use crate::{Context, Error};
 pub struct StatType {
@@ -47,44 +49,86 @@ impl StatType {
     }
 }
 
+/// Roll several dice and keep the best one, which is the lowest, or the worst one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum Edge {
+    Advantage,
+    Disadvantage,
+}
+
+/// Options chosen for a roll when the command is sent
+#[derive(Debug, Clone, Default)]
+pub struct RollOptions {
+    pub secret: bool,                   // Only the game master will see the result
+    pub situational_modifier: i32,      // A bonus or malus applied to this roll only
+    pub edge: Option<(Edge, u32)>, // An advantage or disadvantage, with the number of dice rolled
+    pub difficulty: Option<Difficulty>, // The difficulty tier of the roll, normal if not set
+}
+
+impl RollOptions {
+    /// Build the edge of a roll from the command arguments, 2 dice are rolled by default
+    pub fn edge_from(edge: Option<Edge>, dice: Option<u32>) -> Option<(Edge, u32)> {
+        edge.map(|e| (e, dice.unwrap_or(2)))
+    }
+}
+
 pub struct RollResult {
     pub stat: Option<String>,
     pub stat_type: StatType,
     pub player_name: String,
+    pub player_discord_name: Option<String>,
+    pub player_file: Option<String>,
+    pub rolled_by: Option<String>,
     pub roll: i32,
+    pub rolls: Vec<i32>, // Every dice rolled, when rolling with an advantage or disadvantage
     pub mastery: Option<i32>,
     pub new_mastery: Option<i32>,
     pub modifier: Option<i32>,
-    pub successful: Option<bool>,
+    pub situational_modifier: Option<i32>,
+    pub difficulty: Option<i32>, // The base difficulty of the stat, subtracted from the threshold
+    pub difficulty_tier: Option<Difficulty>, // The difficulty tier chosen for the roll, if any
+    pub outcome: Option<Outcome>,
+    pub experience_earned: Option<i32>,
+    pub secret: bool,
 }
 
 impl RollResult {
     #[allow(clippy::too_many_arguments)]
     pub fn with_player(
         stat: &str,
-        player: Player,
+        player: &Player,
         affinities: &[Affinity],
         roll: i32,
         mastery: i32,
         new_mastery: i32,
         modifier: i32,
-        successful: bool,
+        outcome: Outcome,
+        experience_earned: i32,
     ) -> Result<Self, Error> {
         let stat_type = StatType {
             is_talent: player.is_talent(stat),
             is_major_affinity: player.is_major_affinity(stat, affinities)?,
             is_minor_affinity: player.is_minor_affinity(stat, affinities)?,
         };
-        let player_name = player.name;
+        let player_name = player.name.to_string();
         Ok(Self {
             stat: Some(stat.to_string()),
             stat_type,
             player_name,
+            player_discord_name: Some(player.discord_name.to_string()),
+            player_file: Some(player.path().to_string()),
+            rolled_by: None,
             roll,
+            rolls: vec![roll],
             mastery: Some(mastery),
             new_mastery: Some(new_mastery),
             modifier: Some(modifier),
-            successful: Some(successful),
+            situational_modifier: None,
+            difficulty: None,
+            difficulty_tier: None,
+            outcome: Some(outcome),
+            experience_earned: Some(experience_earned),
+            secret: false,
         })
     }
 
@@ -97,63 +141,102 @@ impl RollResult {
                 is_minor_affinity: false,
             },
             player_name: player_name.to_string(),
+            player_discord_name: None,
+            player_file: None,
+            rolled_by: None,
             roll,
+            rolls: vec![roll],
             mastery: None,
             new_mastery: None,
             modifier: None,
-            successful: None,
+            situational_modifier: None,
+            difficulty: None,
+            difficulty_tier: None,
+            outcome: None,
+            experience_earned: None,
+            secret: false,
         }
     }
 }
 
-fn get_roll(config: &Config) -> i32 {
-    match config.roll_command_statistic_law {
-        StatisticLaw::Uniform => {
-            let mut rng: StdRng = rand::SeedableRng::from_entropy();
-            rng.gen_range(1..101)
+/// Roll as many dice as the edge requires, and keep the best or worst one
+fn get_rolls(
+    roll_rules: &RollRules,
+    options: &RollOptions,
+    rng: &mut impl Rng,
+) -> Result<(i32, Vec<i32>), Error> {
+    let law = roll_rules.statistic_law;
+    match options.edge {
+        None => {
+            let roll = law.roll(rng)?;
+            Ok((roll, vec![roll]))
+        }
+        Some((edge, dice)) => {
+            let rolls = (0..dice.max(1))
+                .map(|_| law.roll(rng))
+                .collect::<Result<Vec<i32>, Error>>()?;
+            let kept = match edge {
+                Edge::Advantage => rolls.iter().min(),
+                Edge::Disadvantage => rolls.iter().max(),
+            };
+            Ok((*kept.unwrap_or(&rolls[0]), rolls))
         }
-        StatisticLaw::Normal(mean, std_dev) => Normal::new(mean, std_dev)
-            .unwrap()
-            .sample(&mut rand::thread_rng())
-            .clamp(1.0, 100.0) as i32,
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn get_roll_result(
     discord_name: Option<&str>,
-    player_path: Option<&str>,
+    player: Option<&mut Player>,
     affinities: Option<&[Affinity]>,
     stat: Option<&Stat>,
     config: &Config,
+    roll_rules: &RollRules,
+    options: &RollOptions,
+    rng: &mut impl Rng,
 ) -> Result<RollResult, Error> {
     // Roll a dice
-    let roll = get_roll(config);
-    info!("Rolled a {roll}");
+    let (roll, rolls) = get_rolls(roll_rules, options, rng)?;
+    info!("Rolled a {roll} out of {rolls:?}");
 
-    if let Some(p_path) = player_path {
+    let mut roll_result = if let Some(p) = player {
         if let Some(stat) = stat {
             if let Some(affinities) = affinities {
                 // Prepare info for the final message
-                let mut p = Player::from(p_path)?;
                 // Find the limit for a success based on the experience in this stat
-                let mastery = get_mastery(&p, &stat.display_name, config, affinities)?;
+                let mastery = get_mastery(p, &stat.display_name, config, affinities)?;
                 let modifier = p.get_modifier(&stat.display_name);
 
-                let threshold = mastery + modifier;
-                let (successful, experience_earned) = if roll > threshold {
-                    info!("Player {} failed the check: {roll}/{threshold}", p.name);
-                    (false, config.experience_earned_after_failure)
+                let threshold = config
+                    .difficulties
+                    .tier(options.difficulty.unwrap_or(Difficulty::Normal))
+                    .apply(
+                        mastery + modifier + options.situational_modifier - roll_rules.difficulty,
+                    );
+                let outcome = config.outcomes.outcome(roll, threshold);
+                info!(
+                    "Player {} got a {} on the check: {roll}/{threshold}",
+                    p.name,
+                    outcome.title()
+                );
+                let experience_earned = roll_rules.experience(config.experience_earned(&outcome));
+
+                let experience_earned = if options.secret && !config.secret_rolls_grant_experience {
+                    info!(
+                        "Secret roll, the experience of {} is left unchanged",
+                        p.name
+                    );
+                    0
+                } else if let Err(e) = p.increase_experience(experience_earned, &stat.display_name)
+                {
+                    error!("Something went wrong when updating the player experience: {e}");
+                    0
                 } else {
-                    info!("Player {} passed the check: {roll}/{threshold}", p.name);
-                    (true, config.experience_earned_after_success)
+                    experience_earned
                 };
+                let new_mastery = get_mastery(p, &stat.display_name, config, affinities)?;
 
-                if let Err(e) = p.increase_experience(experience_earned, &stat.display_name) {
-                    error!("Something went wrong when updating the player experience: {e}")
-                }
-                let new_mastery = get_mastery(&p, &stat.display_name, config, affinities)?;
-
-                Ok(RollResult::with_player(
+                let mut roll_result = RollResult::with_player(
                     &stat.display_name,
                     p,
                     affinities,
@@ -161,33 +244,41 @@ fn get_roll_result(
                     mastery,
                     new_mastery,
                     modifier,
-                    successful,
-                )?)
+                    outcome,
+                    experience_earned,
+                )?;
+                roll_result.situational_modifier = Some(options.situational_modifier);
+                roll_result.difficulty = Some(roll_rules.difficulty);
+                roll_result.difficulty_tier = options.difficulty;
+                roll_result
             } else {
-                Err("If player is specified affinities should be specified too".into())
+                return Err("If player is specified affinities should be specified too".into());
             }
         } else {
-            Err("If player is specified a stat should be specified too".into())
+            return Err("If player is specified a stat should be specified too".into());
         }
     } else {
-        Ok(RollResult::new(
+        RollResult::new(
             roll,
             discord_name.ok_or("No player or discord name specified")?,
-        ))
-    }
+        )
+    };
+    roll_result.rolls = rolls;
+    roll_result.secret = options.secret;
+    Ok(roll_result)
 }
 
 /// Updates the initial message until the user clicked an actual stat (leaf in the stat tree)
-/// Handle the recursion needed to go through the stat tree
+/// Handle the recursion needed to go through the stat tree.
+/// The stat families the user navigated through are kept to be able to go back to the parent family
 #[async_recursion]
-async fn choose_stat(
+async fn choose_stat<'a>(
     ctx: &Context<'_>,
     interaction: ComponentInteraction,
-    player_path: &str,
-    affinities: &[Affinity],
-    stats: &[Stat],
-    config: &Config,
-) -> Result<(RollResult, ComponentInteraction), Error> {
+    stats: &'a [Stat],
+    mut families: Vec<&'a Stat>,
+    user_id: UserId,
+) -> Result<(Stat, ComponentInteraction), Error> {
     let res_id = interaction.data.custom_id.to_string();
 
     // Get the stat selected by the user
@@ -195,34 +286,32 @@ async fn choose_stat(
         finish_interaction(ctx, interaction, "Command aborted").await?;
         return Err("Aborted by user".into());
     }
-    let stat = stats.iter().find(|&s| s.id == res_id).unwrap().clone();
+    if res_id == "back" {
+        let family = families
+            .pop()
+            .ok_or("Cannot go back from the root of the stat tree")?;
+        info!("Going back from stat family {}", family.display_name);
+        let interaction =
+            send_choose_stats_message(ctx, Some(interaction), stats, &families, user_id).await?;
+        return choose_stat(ctx, interaction, stats, families, user_id).await;
+    }
+    let stat = get_current_level(stats, &families)
+        .iter()
+        .find(|&s| s.id == res_id)
+        .ok_or(format!("Stat {res_id} not found"))?;
     info!("Selected stat {}", stat.display_name);
 
     // If the stat has substats, we should let the user select one
     if !stat.sub_stats.is_empty() {
         // Recursion to check the stat chosen by the user
+        families.push(stat);
         let interaction =
-            send_choose_stats_message(ctx, Some(interaction), &stat.sub_stats).await?;
-        choose_stat(
-            ctx,
-            interaction,
-            player_path,
-            affinities,
-            &stat.sub_stats,
-            config,
-        )
-        .await
+            send_choose_stats_message(ctx, Some(interaction), stats, &families, user_id).await?;
+        choose_stat(ctx, interaction, stats, families, user_id).await
     }
     // The stat has no substats, time to end the recursion
     else {
-        let roll_result = get_roll_result(
-            None,
-            Some(player_path),
-            Some(affinities),
-            Some(&stat),
-            config,
-        )?;
-        Ok((roll_result, interaction))
+        Ok((stat.clone(), interaction))
     }
 }
 
@@ -244,42 +333,371 @@ async fn proceed_without_player_stats(
     }
 }
 
+/// Find the stat given as a command argument, warning the user if it doesn't exist
+pub async fn find_stat_argument(ctx: &Context<'_>, input: &str) -> Result<Stat, Error> {
+    match Stat::find(&get_campaign(ctx).await?.rules().stats, input) {
+        Some(stat) => Ok(stat),
+        None => {
+            ctx.send(
+                CreateReply::default()
+                    .ephemeral(true)
+                    .content(format!("Unknown stat {input:?}.")),
+            )
+            .await?;
+            Err(format!("Unknown stat {input:?}").into())
+        }
+    }
+}
+
+/// Get the stat to roll for, either the given stat or by guiding the user through the stat tree.
+/// A message asking to choose a stat is sent by the command if there is no interaction to start from
+pub async fn select_stat(
+    ctx: &Context<'_>,
+    interaction: Option<ComponentInteraction>,
+    stat: Option<Stat>,
+    user_id: UserId,
+) -> Result<(Stat, Option<ComponentInteraction>), Error> {
+    let stats = &get_campaign(ctx).await?.rules().stats;
+    let families = match stat {
+        // A stat was given as argument, no need to go through the stat tree
+        Some(stat) if stat.sub_stats.is_empty() => {
+            info!("Selected stat {} from argument", stat.display_name);
+            return Ok((stat, interaction));
+        }
+        // A stat family was given as argument, start the stat tree at this node
+        Some(family) => {
+            info!("Selected stat family {} from argument", family.display_name);
+            Stat::path_to(stats, &family.id).ok_or("Stat family not found")?
+        }
+        None => vec![],
+    };
+    // Guide the user through the stat tree to choose a stat
+    let interaction =
+        send_choose_stats_message(ctx, interaction, stats, &families, user_id).await?;
+    let (stat, interaction) = choose_stat(ctx, interaction, stats, families, user_id).await?;
+    Ok((stat, Some(interaction)))
+}
+
+/// Roll for the player represented by this file, updating their experience
+pub async fn roll_for_player(
+    ctx: &Context<'_>,
+    player_file: &str,
+    stat: &Stat,
+    options: &RollOptions,
+) -> Result<RollResult, Error> {
+    let campaign = get_campaign(ctx).await?;
+    let rules = campaign.rules();
+    // The difficulty set by the game master wins over the one chosen by the player
+    let mut options = options.clone();
+    if let Some(difficulty) = campaign.take_next_difficulty(player_file).await {
+        if options.difficulty.is_none() || !is_game_master(ctx).await {
+            info!("Using the {difficulty} difficulty set by the game master");
+            options.difficulty = Some(difficulty);
+        }
+    }
+    campaign
+        .update_player(player_file, |p| {
+            ctx.data().rng.with(|rng| {
+                get_roll_result(
+                    None,
+                    Some(p),
+                    Some(&rules.affinities),
+                    Some(stat),
+                    &rules.config,
+                    &rules.roll_rules(stat),
+                    &options,
+                    rng,
+                )
+            })
+        })
+        .await
+}
+
+/// Roll for a player, either for the given stat or by guiding the user through the stat tree
+pub async fn roll_with_player(
+    ctx: &Context<'_>,
+    player_file: &str,
+    stat: Option<Stat>,
+    options: &RollOptions,
+) -> Result<(RollResult, Option<ComponentInteraction>), Error> {
+    let (stat, interaction) = select_stat(ctx, None, stat, ctx.author().id).await?;
+    let roll_result = roll_for_player(ctx, player_file, &stat, options).await?;
+    Ok((roll_result, interaction))
+}
+
 /// Roll a dice for the stat you choose. Your experience will be updated based on the result.
 #[poise::command(slash_command)]
-pub async fn roll(ctx: Context<'_>) -> Result<(), Error> {
+pub async fn roll(
+    ctx: Context<'_>,
+    #[description = "The stat or stat family to roll for, skips the stat selection"]
+    #[autocomplete = "autocomplete_stat"]
+    stat: Option<String>,
+    #[description = "A temporary bonus (or malus if negative) for this roll only"]
+    #[min = -100]
+    #[max = 100]
+    modifier: Option<i32>,
+    #[description = "Roll several dice and keep the best (advantage) or the worst (disadvantage)"]
+    edge: Option<Edge>,
+    #[description = "The number of dice rolled with an advantage or disadvantage, 2 by default"]
+    #[min = 2]
+    #[max = 10]
+    edge_dice: Option<u32>,
+    #[description = "The difficulty of the roll, normal by default"] difficulty: Option<Difficulty>,
+    #[description = "Only show the result to the game master"] secret: Option<bool>,
+) -> Result<(), Error> {
     let discord_name = &ctx.author().name;
+    let campaign = get_campaign(&ctx).await?;
+    let rules = campaign.rules();
+    let options = RollOptions {
+        secret: check_secret_argument(&ctx, secret).await?,
+        situational_modifier: modifier.unwrap_or(0),
+        edge: RollOptions::edge_from(edge, edge_dice),
+        difficulty,
+    };
+
+    let stat = match stat {
+        Some(input) => Some(find_stat_argument(&ctx, &input).await?),
+        None => None,
+    };
 
-    // Getting info for the player from his discord name
+    // Getting info for the player from his discord account
     info!("Retrieving player info for {discord_name}");
-    let player = ctx.data().players.get(discord_name);
-    let is_game_master = discord_name == &ctx.data().config.game_master_discord_name;
-    let (roll_result, interaction) = if player.is_none() && !is_game_master {
+    let player = campaign.get_player(ctx.author()).await;
+    let is_game_master = is_game_master(&ctx).await;
+    let (roll_result, interaction) = if let Some(player) = player {
+        info!("Successfully retrieved player info for {discord_name}");
+        roll_with_player(&ctx, player.path(), stat, &options).await?
+    } else if !is_game_master {
         warn!("Could not find info for player {discord_name}");
         let interaction = proceed_without_player_stats(&ctx, discord_name).await?;
         info!("Proceeding without info");
-        let roll_result =
-            get_roll_result(Some(discord_name), None, None, None, &ctx.data().config)?;
+        let roll_result = ctx.data().rng.with(|rng| {
+            get_roll_result(
+                Some(discord_name),
+                None,
+                None,
+                None,
+                &rules.config,
+                &RollRules::from_config(&rules.config),
+                &options,
+                rng,
+            )
+        })?;
         (roll_result, Some(interaction))
-    } else if player.is_none() && is_game_master {
+    } else {
         info!("Skipping player info retrieval for game master");
-        let roll_result =
-            get_roll_result(Some(discord_name), None, None, None, &ctx.data().config)?;
+        let roll_result = ctx.data().rng.with(|rng| {
+            get_roll_result(
+                Some(discord_name),
+                None,
+                None,
+                None,
+                &rules.config,
+                &RollRules::from_config(&rules.config),
+                &options,
+                rng,
+            )
+        })?;
         (roll_result, None)
-    } else {
-        info!("Successfully retrieved player info for {discord_name}");
-        let interaction = send_choose_stats_message(&ctx, None, &ctx.data().stats).await?;
-        // Guide the user through the stat tree to choose a stat
-        let (roll_result, interaction) = choose_stat(
-            &ctx,
-            interaction,
-            player.ok_or("Invalid player")?,
-            &ctx.data().affinities,
-            &ctx.data().stats,
-            &ctx.data().config,
-        )
-        .await?;
-        (roll_result, Some(interaction))
     };
     display_result(&ctx, interaction, &roll_result).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{get_roll_result, get_rolls, Edge, RollOptions, RollResult};
+    use crate::config::difficulty::Difficulty;
+    use crate::config::players::Player;
+    use crate::config::stat::Stat;
+    use crate::config::stat_rules::RollRules;
+    use crate::config::Config;
+    use crate::test_utils::{config, TempDir};
+
+    fn agility() -> Stat {
+        Stat {
+            id: "agilite".to_string(),
+            display_name: "Agilité".to_string(),
+            sub_stats: vec![],
+            ..Default::default()
+        }
+    }
+
+    fn roll(player: &mut Player, config: &Config, options: &RollOptions, seed: u64) -> RollResult {
+        roll_with_rules(
+            player,
+            config,
+            &RollRules::from_config(config),
+            options,
+            seed,
+        )
+    }
+
+    fn roll_with_rules(
+        player: &mut Player,
+        config: &Config,
+        roll_rules: &RollRules,
+        options: &RollOptions,
+        seed: u64,
+    ) -> RollResult {
+        get_roll_result(
+            None,
+            Some(player),
+            Some(&[]),
+            Some(&agility()),
+            config,
+            roll_rules,
+            options,
+            &mut StdRng::seed_from_u64(seed),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn success_grants_experience() {
+        let temp_dir = TempDir::new();
+        let mut player = temp_dir.player();
+        let options = RollOptions {
+            situational_modifier: 200,
+            ..Default::default()
+        };
+        for seed in 0..10 {
+            let roll_result = roll(&mut player, &config("{}"), &options, seed);
+            assert!(roll_result.outcome.unwrap().is_success());
+            assert_eq!(roll_result.experience_earned, Some(2));
+        }
+        assert_eq!(player.stats["Agilité"], 62);
+        // The experience is saved in the player file
+        assert_eq!(Player::from(player.path()).unwrap().stats["Agilité"], 62);
+    }
+
+    #[test]
+    fn failure_grants_experience() {
+        let temp_dir = TempDir::new();
+        let mut player = temp_dir.player();
+        let options = RollOptions {
+            situational_modifier: -200,
+            ..Default::default()
+        };
+        for seed in 0..10 {
+            let roll_result = roll(&mut player, &config("{}"), &options, seed);
+            assert!(!roll_result.outcome.unwrap().is_success());
+            assert_eq!(roll_result.experience_earned, Some(1));
+        }
+        assert_eq!(player.stats["Agilité"], 52);
+    }
+
+    #[test]
+    fn secret_rolls_can_grant_no_experience() {
+        let temp_dir = TempDir::new();
+        let mut player = temp_dir.player();
+        let options = RollOptions {
+            secret: true,
+            ..Default::default()
+        };
+        let roll_result = roll(
+            &mut player,
+            &config(r#"{"secret_rolls_grant_experience": false}"#),
+            &options,
+            0,
+        );
+        assert_eq!(roll_result.experience_earned, Some(0));
+        assert_eq!(player.stats["Agilité"], 42);
+        let roll_result = roll(&mut player, &config("{}"), &options, 0);
+        assert!(roll_result.experience_earned > Some(0));
+    }
+
+    #[test]
+    fn stat_rules_change_the_threshold_and_experience() {
+        let temp_dir = TempDir::new();
+        let mut player = temp_dir.player();
+        let config = config("{}");
+        let roll_rules = RollRules {
+            difficulty: 300,
+            experience_multiplier: 5.0,
+            ..RollRules::from_config(&config)
+        };
+        let options = RollOptions {
+            situational_modifier: 200,
+            ..Default::default()
+        };
+        let roll_result = roll_with_rules(&mut player, &config, &roll_rules, &options, 0);
+        assert!(!roll_result.outcome.unwrap().is_success());
+        assert_eq!(roll_result.difficulty, Some(300));
+        assert_eq!(roll_result.experience_earned, Some(5));
+        assert_eq!(player.stats["Agilité"], 47);
+    }
+
+    #[test]
+    fn difficulty_tiers_change_the_threshold() {
+        let config = config("{}");
+        let margin = |difficulty: Option<Difficulty>| {
+            let options = RollOptions {
+                difficulty,
+                ..Default::default()
+            };
+            let temp_dir = TempDir::new();
+            let mut player = temp_dir.player();
+            let roll_result = roll(&mut player, &config, &options, 0);
+            assert_eq!(roll_result.difficulty_tier, difficulty);
+            roll_result.outcome.unwrap().margin()
+        };
+        let normal = margin(None);
+        assert_eq!(margin(Some(Difficulty::Normal)), normal);
+        assert_eq!(margin(Some(Difficulty::Easy)), normal + 20);
+        assert_eq!(margin(Some(Difficulty::Heroic)), normal - 40);
+    }
+
+    #[test]
+    fn same_seed_same_roll() {
+        let config = config("{}");
+        let options = RollOptions::default();
+        let rolls: Vec<i32> = (0..20)
+            .map(|seed| {
+                get_rolls(
+                    &RollRules::from_config(&config),
+                    &options,
+                    &mut StdRng::seed_from_u64(seed),
+                )
+                .unwrap()
+                .0
+            })
+            .collect();
+        let rolled_again: Vec<i32> = (0..20)
+            .map(|seed| {
+                get_rolls(
+                    &RollRules::from_config(&config),
+                    &options,
+                    &mut StdRng::seed_from_u64(seed),
+                )
+                .unwrap()
+                .0
+            })
+            .collect();
+        assert_eq!(rolls, rolled_again);
+        assert!(rolls.iter().all(|r| (1..=100).contains(r)));
+    }
+
+    #[test]
+    fn edge_keeps_the_best_or_worst_dice() {
+        let config = config("{}");
+        let mut rng = StdRng::seed_from_u64(42);
+        for edge in [Edge::Advantage, Edge::Disadvantage] {
+            let options = RollOptions {
+                edge: Some((edge, 3)),
+                ..Default::default()
+            };
+            let (roll, rolls) =
+                get_rolls(&RollRules::from_config(&config), &options, &mut rng).unwrap();
+            assert_eq!(rolls.len(), 3);
+            let expected = match edge {
+                Edge::Advantage => rolls.iter().min(),
+                Edge::Disadvantage => rolls.iter().max(),
+            };
+            assert_eq!(Some(&roll), expected);
+        }
+    }
+}
@@ -2,10 +2,10 @@ use poise::serenity_prelude::CreateEmbed;
 use poise::CreateReply;
 
 use crate::config::affinity::Affinity;
+use crate::config::players::Player;
 use crate::config::Config;
-use crate::Player;
 
-use super::utils::get_mastery;
+use super::utils::{get_campaign, get_mastery};
 use crate::{Context, Error};
 
 static DISCORD_FIELD_LIMIT: usize = 25;
@@ -27,11 +27,10 @@ fn format_stat_infos(
 /// Display the summary of the player's stats.
 #[poise::command(slash_command)]
 pub async fn summary(ctx: Context<'_>) -> Result<(), Error> {
-    let discord_name = &ctx.author().name;
-    let player_path = ctx.data().players.get(discord_name);
-    match player_path {
-        Some(p) => {
-            let player = Player::from(p)?;
+    let campaign = get_campaign(&ctx).await?;
+    match campaign.get_player(ctx.author()).await {
+        Some(player) => {
+            let rules = campaign.rules();
             let stats: Vec<&str> = player.stats.keys().map(|key| key.as_str()).collect();
             let page_number = stats.len() / DISCORD_FIELD_LIMIT + 1;
 
@@ -52,8 +51,8 @@ pub async fn summary(ctx: Context<'_>) -> Result<(), Error> {
                                     format_stat_infos(
                                         &player,
                                         stat,
-                                        &ctx.data().config,
-                                        &ctx.data().affinities,
+                                        &rules.config,
+                                        &rules.affinities,
                                     )
                                     .unwrap(),
                                     true,
@@ -65,11 +64,9 @@ pub async fn summary(ctx: Context<'_>) -> Result<(), Error> {
             }
         }
         None => {
-            ctx.send(
-                CreateReply::default()
-                    .ephemeral(true)
-                    .content("You don't have player data yet."),
-            )
+            ctx.send(CreateReply::default().ephemeral(true).content(
+                "You don't have player data yet, ask the game master for one with /claim.",
+            ))
             .await?;
         }
     }
@@ -0,0 +1,109 @@
+use poise::serenity_prelude::{ChannelId, CreateEmbed, EditMessage, MessageId};
+use poise::CreateReply;
+use tracing::{error, info, warn};
+
+use crate::history::RollRecord;
+use crate::{Context, Error};
+
+use super::utils::{game_master_only, get_campaign};
+
+/// Remove the experience the player earned with a roll
+async fn revert_experience(ctx: &Context<'_>, record: &RollRecord) -> Result<(), Error> {
+    if let (Some(stat), Some(experience)) = (&record.stat, record.experience_earned) {
+        if experience != 0 {
+            let campaign = get_campaign(ctx).await?;
+            // Older records only know the discord name of the player
+            let player_file = match (&record.player_file, &record.player_discord_name) {
+                (Some(file), _) => file.to_string(),
+                (None, Some(discord_name)) => campaign
+                    .find_player(discord_name)
+                    .await
+                    .ok_or(format!("No player found for {discord_name}"))?
+                    .path()
+                    .to_string(),
+                (None, None) => return Ok(()),
+            };
+            campaign
+                .update_player(&player_file, |p| p.decrease_experience(experience, stat))
+                .await?;
+            info!(
+                "Removed {experience} xp in {stat} from {}",
+                record.player_name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Update the message of the roll to show that it was cancelled
+async fn mark_message_as_cancelled(ctx: &Context<'_>, record: &RollRecord) -> Result<(), Error> {
+    // Secret rolls are sent as ephemeral messages, which can't be edited later on
+    let message_id = match record.message_id {
+        Some(id) if !record.secret => MessageId::new(id),
+        _ => return Ok(()),
+    };
+    let channel_id = ChannelId::new(record.channel_id);
+    let message = channel_id.message(ctx, message_id).await?;
+    let embed = match message.embeds.first() {
+        Some(embed) => {
+            let title = embed.title.clone().unwrap_or_default();
+            CreateEmbed::from(embed.clone()).title(format!("~~{title}~~ CANCELLED"))
+        }
+        None => CreateEmbed::default().title("CANCELLED"),
+    };
+    channel_id
+        .edit_message(ctx, message_id, EditMessage::new().embed(embed))
+        .await?;
+    Ok(())
+}
+
+/// Cancel a roll and remove the experience it gave. Only game masters can use this command.
+#[poise::command(slash_command, check = "game_master_only")]
+pub async fn undo(
+    ctx: Context<'_>,
+    #[description = "Number of the roll to cancel, as shown by /history. The last roll by default"]
+    roll: Option<u64>,
+) -> Result<(), Error> {
+    let history = &get_campaign(&ctx).await?.history;
+    // The roll is voided first so that two /undo can't revert the same experience twice
+    let record = match history.void(roll).await {
+        Ok(r) => r,
+        Err(e) => {
+            ctx.send(
+                CreateReply::default()
+                    .ephemeral(true)
+                    .content(format!("{e}.")),
+            )
+            .await?;
+            return Err(e);
+        }
+    };
+    info!("Cancelling roll #{} of {}", record.id, record.player_name);
+
+    if let Err(e) = revert_experience(&ctx, &record).await {
+        if let Err(e) = history.restore(record.id).await {
+            error!("Could not restore roll #{}: {e}", record.id);
+        }
+        ctx.send(CreateReply::default().ephemeral(true).content(format!(
+            "Could not remove the experience of roll #{}, it was not cancelled: {e}.",
+            record.id
+        )))
+        .await?;
+        return Err(e);
+    }
+    if let Err(e) = mark_message_as_cancelled(&ctx, &record).await {
+        warn!("Could not edit the message of roll #{}: {e}", record.id);
+    }
+
+    let mut content = format!("Cancelled roll #{} of {}", record.id, record.player_name);
+    if let (Some(stat), Some(experience)) = (&record.stat, record.experience_earned) {
+        content += &format!(" (-{experience} xp in {stat})");
+    }
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .content(content + "."),
+    )
+    .await?;
+    Ok(())
+}
@@ -1,14 +1,19 @@
 use std::time::Duration;
 
 use poise::serenity_prelude::{
-    ButtonStyle, ComponentInteraction, ComponentInteractionCollector, CreateActionRow,
-    CreateButton, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+    AutocompleteChoice, ButtonStyle, Colour, ComponentInteraction, ComponentInteractionCollector,
+    CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseMessage, MessageId, ReactionType, UserId,
 };
 use poise::CreateReply;
-use tracing::info;
+use tracing::{error, info};
 
 use crate::{
-    config::{affinity::Affinity, players::Player, stat::Stat, Config},
+    campaign::Campaign,
+    config::{
+        affinity::Affinity, outcome::Outcome, parser::fuzzy_score, players::Player, stat::Stat,
+        Config,
+    },
     State,
 };
 
@@ -17,37 +22,179 @@ use super::roll::RollResult;
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, State, Error>;
 
+/// Maximum number of choices Discord accepts for an autocomplete
+static AUTOCOMPLETE_LIMIT: usize = 25;
+/// Maximum number of characters of a Discord message
+static MESSAGE_LENGTH_LIMIT: usize = 2000;
+
 /// Build a button based on an id and display string
 pub fn button(id: &str, display_name: &str, style: ButtonStyle) -> CreateButton {
     CreateButton::new(id).label(display_name).style(style)
 }
 
-pub fn get_stats_buttons(stats: &[Stat]) -> Vec<CreateActionRow> {
-    let mut buttons = stats
+pub fn get_stats_buttons(stats: &[Stat], can_go_back: bool) -> Vec<CreateActionRow> {
+    let visible_stats: Vec<&Stat> = stats.iter().filter(|s| !s.hidden).collect();
+    let mut buttons = visible_stats
         .chunks(5)
         .map(|chunk| {
             CreateActionRow::Buttons(
                 chunk
                     .iter()
                     .map(|stat| {
-                        let style = match stat.sub_stats.is_empty() {
-                            true => ButtonStyle::Secondary,
-                            false => ButtonStyle::Success,
+                        let style = match (stat.colour, stat.sub_stats.is_empty()) {
+                            (Some(colour), _) => colour.style(),
+                            (None, true) => ButtonStyle::Secondary,
+                            (None, false) => ButtonStyle::Success,
                         };
-                        button(&stat.id, &stat.display_name, style)
+                        let mut button = button(&stat.id, &stat.display_name, style);
+                        if let Some(emoji) = stat
+                            .emoji
+                            .as_deref()
+                            .and_then(|e| ReactionType::try_from(e).ok())
+                        {
+                            button = button.emoji(emoji);
+                        }
+                        button
                     })
                     .collect::<Vec<_>>(),
             )
         })
         .collect::<Vec<_>>();
-    buttons.push(CreateActionRow::Buttons(vec![button(
-        "abort",
-        "Abort",
-        ButtonStyle::Danger,
-    )]));
+    let mut navigation_buttons = vec![];
+    if can_go_back {
+        navigation_buttons.push(button("back", "Back", ButtonStyle::Primary));
+    }
+    navigation_buttons.push(button("abort", "Abort", ButtonStyle::Danger));
+    buttons.push(CreateActionRow::Buttons(navigation_buttons));
     buttons
 }
 
+/// Get the campaign played on the server of the command, telling the user if there is none
+pub async fn get_campaign<'a>(ctx: &Context<'a>) -> Result<&'a Campaign, Error> {
+    match ctx.data().campaign(ctx.guild_id()) {
+        Some(campaign) => Ok(campaign),
+        None => {
+            ctx.send(
+                CreateReply::default()
+                    .ephemeral(true)
+                    .content("No campaign is played on this server."),
+            )
+            .await?;
+            Err(format!("No campaign for server {:?}", ctx.guild_id()).into())
+        }
+    }
+}
+
+/// Suggest the stats and stat families matching what the user typed so far
+pub async fn autocomplete_stat(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let Some(campaign) = ctx.data().campaign(ctx.guild_id()) else {
+        return vec![];
+    };
+    Stat::search(&campaign.rules().stats, partial)
+        .into_iter()
+        .take(AUTOCOMPLETE_LIMIT)
+        .map(|stat| {
+            let mut label = stat.display_name.clone();
+            if let Some((_, Some(alias))) = stat.match_score(partial) {
+                label += &format!(" ({alias})");
+            }
+            if !stat.sub_stats.is_empty() {
+                label += " (family)";
+            }
+            AutocompleteChoice::new(label, stat.id)
+        })
+        .collect()
+}
+
+/// Suggest the players matching what the user typed so far, by player name or discord name
+pub async fn autocomplete_player(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let Some(campaign) = ctx.data().campaign(ctx.guild_id()) else {
+        return vec![];
+    };
+    let mut matches: Vec<(usize, Player)> = campaign
+        .get_players()
+        .await
+        .into_iter()
+        .filter_map(|p| {
+            let score = [
+                fuzzy_score(&p.name, partial),
+                fuzzy_score(&p.discord_name, partial),
+            ]
+            .into_iter()
+            .flatten()
+            .min()?;
+            Some((score, p))
+        })
+        .collect();
+    matches.sort_by(|(score1, p1), (score2, p2)| {
+        score1.cmp(score2).then_with(|| p1.name.cmp(&p2.name))
+    });
+    matches
+        .into_iter()
+        .take(AUTOCOMPLETE_LIMIT)
+        // Several players may share a discord name, their file tells them apart
+        .map(|(_, p)| {
+            AutocompleteChoice::new(
+                format!("{} ({})", p.name, p.discord_name),
+                p.path().to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Is the author of the command one of the game masters, either by id or by role?
+pub async fn is_game_master(ctx: &Context<'_>) -> bool {
+    let Some(campaign) = ctx.data().campaign(ctx.guild_id()) else {
+        return false;
+    };
+    let roles = match ctx.author_member().await {
+        Some(member) => member.roles.clone(),
+        None => vec![],
+    };
+    campaign.is_game_master(ctx.author(), &roles).await
+}
+
+/// Was the button clicked by one of the game masters of the campaign?
+pub async fn clicked_by_game_master(
+    campaign: &Campaign,
+    interaction: &ComponentInteraction,
+) -> bool {
+    let roles = match &interaction.member {
+        Some(member) => member.roles.clone(),
+        None => vec![],
+    };
+    campaign.is_game_master(&interaction.user, &roles).await
+}
+
+/// Poise check for the commands only the game masters can use, telling the others they can't
+pub async fn game_master_only(ctx: Context<'_>) -> Result<bool, Error> {
+    let action = format!("use /{}", ctx.command().name);
+    Ok(check_game_master(&ctx, &action).await.is_ok())
+}
+
+/// Check that the author of the command is a game master, and tell them otherwise
+pub async fn check_game_master(ctx: &Context<'_>, action: &str) -> Result<(), Error> {
+    if !is_game_master(ctx).await {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!("Only the game master can {action}.")),
+        )
+        .await?;
+        return Err(format!("{} is not the game master", ctx.author().name).into());
+    }
+    Ok(())
+}
+
+/// Check that only game masters ask for a secret roll, and tell the others they can't
+pub async fn check_secret_argument(ctx: &Context<'_>, secret: Option<bool>) -> Result<bool, Error> {
+    let secret = secret.unwrap_or(false);
+    if secret {
+        check_game_master(ctx, "roll secretly").await?;
+    }
+    Ok(secret)
+}
+
 /// Build a row with a yes and a no button
 pub fn yes_no_buttons() -> CreateActionRow {
     CreateActionRow::Buttons(vec![
@@ -56,20 +203,45 @@ pub fn yes_no_buttons() -> CreateActionRow {
     ])
 }
 
-/// Send a message asking to choose between the given stats
+/// Get the stats to choose from after navigating through the given stat families
+pub fn get_current_level<'a>(stats: &'a [Stat], families: &[&'a Stat]) -> &'a [Stat] {
+    match families.last() {
+        Some(family) => &family.sub_stats,
+        None => stats,
+    }
+}
+
+/// Send a message asking to choose between the stats of the last family the user navigated to
 pub async fn send_choose_stats_message(
     ctx: &Context<'_>,
     interaction: Option<ComponentInteraction>,
     stats: &[Stat],
+    families: &[&Stat],
+    user_id: UserId,
 ) -> Result<ComponentInteraction, Error> {
     info!("Asking user to choose a stat");
+    let mut content = "Choose your stat / stat family".to_owned();
+    if !families.is_empty() {
+        let breadcrumb: Vec<&str> = families.iter().map(|f| f.display_name.as_str()).collect();
+        content += &format!("\n**{}**", breadcrumb.join(" › "));
+    }
+    let current_level = get_current_level(stats, families);
+    let descriptions = current_level.iter().filter(|s| !s.hidden).filter_map(|s| {
+        Some(format!(
+            "\n- **{}**: {}",
+            s.display_name,
+            s.description.as_ref()?
+        ))
+    });
+    content = with_lines_up_to(content, descriptions, MESSAGE_LENGTH_LIMIT);
+    let buttons = get_stats_buttons(current_level, !families.is_empty());
     if let Some(int) = interaction {
         int.create_response(
             ctx,
             CreateInteractionResponse::UpdateMessage(
                 CreateInteractionResponseMessage::default()
-                    .content("Choose your stat / stat family")
-                    .components(get_stats_buttons(stats)),
+                    .content(content)
+                    .components(buttons),
             ),
         )
         .await?;
@@ -77,14 +249,56 @@ pub async fn send_choose_stats_message(
         ctx.send(
             CreateReply::default()
                 .ephemeral(true)
-                .content("Choose your stat / stat family")
-                .components(get_stats_buttons(stats)),
+                .content(content)
+                .components(buttons),
         )
         .await?;
     }
 
+    wait_for_click(ctx, user_id).await
+}
+
+/// Append the lines to the content as long as it stays under the limit, marking the missing ones with "…"
+fn with_lines_up_to(
+    mut content: String,
+    lines: impl Iterator<Item = String>,
+    limit: usize,
+) -> String {
+    let ellipsis = "\n…";
+    let mut length = content.chars().count();
+    for line in lines {
+        let line_length = line.chars().count();
+        if length + line_length + ellipsis.chars().count() > limit {
+            content += ellipsis;
+            break;
+        }
+        length += line_length;
+        content += &line;
+    }
+    content
+}
+
+/// Build the id of a button of the command. It is prefixed with the command id,
+/// so that a command never catches the clicks on the buttons of another one
+pub fn command_button_id(ctx: &Context<'_>, name: &str) -> String {
+    format!("{}{name}", ctx.id())
+}
+
+/// Collect the clicks on the buttons built with command_button_id, in the channel of the command
+pub fn command_button_collector(ctx: &Context<'_>) -> ComponentInteractionCollector {
+    let command_id = ctx.id().to_string();
+    ComponentInteractionCollector::new(ctx)
+        .channel_id(ctx.channel_id())
+        .filter(move |i| i.data.custom_id.starts_with(&command_id))
+}
+
+/// Wait for the user to click on a button in the channel of the command
+pub async fn wait_for_click(
+    ctx: &Context<'_>,
+    user_id: UserId,
+) -> Result<ComponentInteraction, Error> {
     Ok(ComponentInteractionCollector::new(ctx)
-        .author_id(ctx.author().id)
+        .author_id(user_id)
         .channel_id(ctx.channel_id())
         .timeout(Duration::from_secs(60))
         .await
@@ -103,12 +317,66 @@ pub async fn send_yes_no_message(
             .components(vec![yes_no_buttons()]),
     )
     .await?;
-    Ok(ComponentInteractionCollector::new(ctx)
+    wait_for_click(ctx, ctx.author().id).await
+}
+
+/// Send an ephemeral embed split into pages, with buttons to navigate between them
+pub async fn send_pages(ctx: &Context<'_>, title: &str, pages: &[String]) -> Result<(), Error> {
+    let previous_id = command_button_id(ctx, "previous");
+    let next_id = command_button_id(ctx, "next");
+    let page_embed = |page: usize| {
+        CreateEmbed::default()
+            .title(title)
+            .description(&pages[page])
+            .footer(CreateEmbedFooter::new(format!(
+                "Page {}/{}",
+                page + 1,
+                pages.len()
+            )))
+    };
+    let navigation_buttons = || {
+        vec![CreateActionRow::Buttons(vec![
+            button(&previous_id, "Previous", ButtonStyle::Secondary),
+            button(&next_id, "Next", ButtonStyle::Secondary),
+        ])]
+    };
+
+    if pages.len() <= 1 {
+        ctx.send(CreateReply::default().ephemeral(true).embed(page_embed(0)))
+            .await?;
+        return Ok(());
+    }
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .embed(page_embed(0))
+            .components(navigation_buttons()),
+    )
+    .await?;
+
+    let mut page = 0;
+    while let Some(interaction) = command_button_collector(ctx)
         .author_id(ctx.author().id)
-        .channel_id(ctx.channel_id())
         .timeout(Duration::from_secs(60))
         .await
-        .ok_or("Interaction failed")?)
+    {
+        page = if interaction.data.custom_id == previous_id {
+            (page + pages.len() - 1) % pages.len()
+        } else {
+            (page + 1) % pages.len()
+        };
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::default()
+                        .embed(page_embed(page))
+                        .components(navigation_buttons()),
+                ),
+            )
+            .await?;
+    }
+    Ok(())
 }
 
 /// Conclude an interaction by updating the message to a non interactive one
@@ -131,14 +399,90 @@ pub async fn finish_interaction(
     Ok(())
 }
 
+/// Send the embed of a roll result, either publicly or only to the game master for secret rolls.
+/// Returns the id of the message containing the embed
+pub async fn send_result(
+    ctx: &Context<'_>,
+    embed: CreateEmbed,
+    secret: bool,
+) -> Result<MessageId, Error> {
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content("")
+                .ephemeral(secret)
+                .embed(embed),
+        )
+        .await?;
+    let message_id = reply.message().await?.id;
+    if secret
+        && get_campaign(ctx)
+            .await?
+            .rules()
+            .config
+            .announce_secret_rolls
+    {
+        ctx.send(CreateReply::default().content("🤫 The game master rolled secretly."))
+            .await?;
+    }
+    Ok(message_id)
+}
+
+/// Display the dice of a roll, highlighting the kept one when rolling with an advantage or disadvantage
+fn format_dice(roll_result: &RollResult) -> String {
+    if roll_result.rolls.len() <= 1 {
+        return format!("*{}*", roll_result.roll);
+    }
+    let kept = roll_result
+        .rolls
+        .iter()
+        .position(|r| *r == roll_result.roll);
+    let dice: Vec<String> = roll_result
+        .rolls
+        .iter()
+        .enumerate()
+        .map(|(i, r)| match Some(i) == kept {
+            true => format!("**{r}**"),
+            false => format!("~~{r}~~"),
+        })
+        .collect();
+    dice.join(" ")
+}
+
+/// Describe a roll in one line: the roll against the threshold, the outcome and the experience earned
+pub fn format_roll(roll_result: &RollResult) -> String {
+    let mut line = roll_result.roll.to_string();
+    if let Some(outcome) = &roll_result.outcome {
+        let threshold = roll_result.roll + outcome.margin();
+        line += &format!(
+            " / {threshold} → {} ({:+})",
+            outcome.title(),
+            outcome.margin()
+        );
+    }
+    if let Some(experience) = roll_result.experience_earned {
+        line += &format!(" (+{experience} xp)");
+    }
+    line
+}
+
+/// The colour of the embed of a roll, so that criticals stand out
+fn outcome_colour(outcome: &Outcome) -> Colour {
+    match outcome {
+        Outcome::CriticalSuccess { .. } => Colour::GOLD,
+        Outcome::Success { .. } => Colour::DARK_GREEN,
+        Outcome::Failure { .. } => Colour::RED,
+        Outcome::CriticalFailure { .. } => Colour::DARK_RED,
+    }
+}
+
 pub async fn display_result(
     ctx: &Context<'_>,
     interaction: Option<ComponentInteraction>,
     roll_result: &RollResult,
 ) -> Result<(), Error> {
-    let title = match &roll_result.successful {
-        Some(true) => "SUCCESS",
-        Some(false) => "FAILURE",
+    let title = match &roll_result.outcome {
+        Some(outcome) => outcome.title(),
         None => "",
     };
     let mut description = format!("**{}**", &roll_result.player_name);
@@ -149,7 +493,7 @@ pub async fn display_result(
         };
         description += &format!(" / *{stat}*{stat_type}");
     }
-    let mut fields = vec![("Roll", format!("*{}*", &roll_result.roll), true)];
+    let mut fields = vec![("Roll", format_dice(roll_result), true)];
     if let Some(mas) = roll_result.mastery {
         let mut mas_display = format!("*{mas}*");
         match roll_result.modifier {
@@ -159,6 +503,19 @@ pub async fn display_result(
         }
         fields.push(("Stat", mas_display, true));
     }
+    if let Some(tier) = roll_result.difficulty_tier {
+        fields.push(("Difficulty", tier.to_string(), true));
+    }
+    // The difficulty of the stat is subtracted from the threshold
+    if let Some(base) = roll_result.difficulty.filter(|d| *d != 0) {
+        fields.push(("Stat difficulty", format!("*{:+}*", -base), true));
+    }
+    if let Some(modif) = roll_result.situational_modifier.filter(|m| *m != 0) {
+        fields.push(("Situational", format!("*{modif:+}*"), true));
+    }
+    if let Some(outcome) = &roll_result.outcome {
+        fields.push(("Margin", format!("*{:+}*", outcome.margin()), true));
+    }
 
     // Acknowledge the interaction and delete the ephemeral interaction
     if let Some(int) = interaction {
@@ -167,23 +524,58 @@ pub async fn display_result(
         int.delete_response(ctx).await?;
     }
 
-    ctx.send(
-        CreateReply::default().content("").ephemeral(false).embed(
-            CreateEmbed::default()
-                .title(title)
-                .description(description)
-                .fields(fields),
-        ),
-    )
-    .await?;
+    let mut embed = CreateEmbed::default()
+        .title(title)
+        .description(description)
+        .fields(fields);
+    if let Some(outcome) = &roll_result.outcome {
+        embed = embed.colour(outcome_colour(outcome));
+    }
+    if let Some(game_master) = &roll_result.rolled_by {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "Rolled by the game master {game_master}"
+        )));
+    }
 
+    let message_id = send_result(ctx, embed, roll_result.secret).await?;
+    record_roll(ctx, roll_result, Some(message_id)).await?;
+    announce_level_up(ctx, roll_result).await
+}
+
+/// Save a roll in the history of the campaign, only logging errors so that the roll is never lost.
+/// The message is only given when it holds this roll alone, since /undo marks the whole message as cancelled
+pub async fn record_roll(
+    ctx: &Context<'_>,
+    roll_result: &RollResult,
+    message_id: Option<MessageId>,
+) -> Result<(), Error> {
+    if let Err(e) = get_campaign(ctx)
+        .await?
+        .history
+        .record(
+            roll_result,
+            &ctx.author().name,
+            ctx.channel_id().get(),
+            message_id.map(|id| id.get()),
+        )
+        .await
+    {
+        error!("Could not save the roll in the history: {e}");
+    }
+    Ok(())
+}
+
+/// Tell the player if the roll increased their mastery of the stat
+pub async fn announce_level_up(ctx: &Context<'_>, roll_result: &RollResult) -> Result<(), Error> {
     if let Some(stat) = &roll_result.stat {
         if let Some(t) = roll_result.mastery {
             if let Some(m) = roll_result.new_mastery {
                 if m > t {
                     // Level up, the threshold will be higher for next rolls
                     ctx.send(
-                        CreateReply::default().content(format!("🎉 Leveled up {stat} to {m}!")),
+                        CreateReply::default()
+                            .ephemeral(roll_result.secret)
+                            .content(format!("🎉 Leveled up {stat} to {m}!")),
                     )
                     .await?;
                 }
@@ -207,17 +599,36 @@ pub fn get_mastery(
     let is_major_affinity = p.is_major_affinity(stat, affinities)?;
     let is_minor_affinity = p.is_minor_affinity(stat, affinities)?;
 
-    // Talent and affinities decrease the coefficient, meaning the player has a lower threshold to success in his roll
-    let mut coefficient = config.learning_constant;
+    // Talent and affinities increase the experience given to the curve, meaning the player has a lower threshold to success in his roll
+    let mut experience = player_experience as f64;
     if is_talent {
-        coefficient *= 1.0 - config.talent_increase_percentage;
+        experience /= 1.0 - config.talent_increase_percentage;
     }
     if is_major_affinity {
-        coefficient *= 1.0 - config.major_affinity_increase_percentage;
+        experience /= 1.0 - config.major_affinity_increase_percentage;
     }
     if is_minor_affinity {
-        coefficient *= 1.0 - config.minor_affinity_increase_percentage;
+        experience /= 1.0 - config.minor_affinity_increase_percentage;
+    }
+    config
+        .mastery_curve
+        .mastery(experience, config.learning_constant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::with_lines_up_to;
+
+    #[test]
+    fn stop_adding_lines_at_the_limit() {
+        let lines = (0..10).map(|i| format!("\nline {i}"));
+        let content = with_lines_up_to("Choose".to_string(), lines, 30);
+        assert_eq!(content, "Choose\nline 0\nline 1\nline 2\n…");
+        assert!(content.chars().count() <= 30);
+        let lines = ["\nline".to_string()].into_iter();
+        assert_eq!(
+            with_lines_up_to("Choose".to_string(), lines, 30),
+            "Choose\nline"
+        );
     }
-    // TODO: allow customization of the function?
-    Ok((100.0 - 99.0 * f64::exp(-player_experience as f64 / coefficient)) as i32)
 }
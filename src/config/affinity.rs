@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::Error;
 
-use super::parser::{clean_input, TreeStructure};
+use super::parser::{clean_string, TreeStructure};
 
 /// Represent an affinity with its name and stats included in this affinity
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -21,7 +21,7 @@ impl TreeStructure for Affinity {
     /// The raw input will be cleaned to be used as an id for the affinity
     fn from_line(raw_line: &str, stats: &[Affinity]) -> Result<Self, Error> {
         Ok(Affinity {
-            id: raw_line.trim().chars().map(clean_input).collect(),
+            id: clean_string(raw_line),
             display_name: raw_line.trim().to_string(),
             stats: stats.to_vec(),
         })
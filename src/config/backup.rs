@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::Error;
+
+use super::players::is_player_file;
+
+// Backups are named after their creation date, so that sorting them by name sorts them by date
+static BACKUP_NAME_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+
+/// Configure how often the player files are saved and how many backups are kept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub folder: String,        // The folder in which the backups are created
+    pub interval_minutes: u64, // The time between two backups while the bot runs, 0 to disable
+    pub max_backups: usize, // The number of backups to keep, the oldest ones are deleted, 0 to keep them all
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            folder: "./backups".to_string(),
+            interval_minutes: 60,
+            max_backups: 20,
+        }
+    }
+}
+
+/// Copy the player files into a new timestamped folder, and delete the oldest backups
+pub fn backup_players(players_folder: &str, config: &BackupConfig) -> Result<(), Error> {
+    let name = chrono::Local::now().format(BACKUP_NAME_FORMAT).to_string();
+    let backup_folder = Path::new(&config.folder).join(&name);
+    fs::create_dir_all(&backup_folder)?;
+    for entry in fs::read_dir(players_folder)? {
+        let path = entry?.path();
+        if !is_player_file(&path) {
+            continue;
+        }
+        if let Some(file_name) = path.file_name() {
+            fs::copy(&path, backup_folder.join(file_name))?;
+        }
+    }
+    info!("Backed up player files to {}", backup_folder.display());
+
+    let mut backups = fs::read_dir(&config.folder)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| is_backup_name(name))
+        .collect::<Vec<String>>();
+    for old_backup in backups_to_remove(&mut backups, config.max_backups) {
+        info!("Removing old backup {old_backup}");
+        fs::remove_dir_all(Path::new(&config.folder).join(old_backup))?;
+    }
+    Ok(())
+}
+
+// The backup folder may contain other folders, that are never deleted
+fn is_backup_name(name: &str) -> bool {
+    chrono::NaiveDateTime::parse_from_str(name, BACKUP_NAME_FORMAT).is_ok()
+}
+
+// Get the oldest backups that exceed the number of backups to keep
+fn backups_to_remove(backups: &mut [String], max_backups: usize) -> Vec<String> {
+    if max_backups == 0 {
+        return vec![];
+    }
+    backups.sort();
+    let excess = backups.len().saturating_sub(max_backups);
+    backups[..excess].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backup_players, backups_to_remove, BackupConfig};
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn remove_oldest_backups() {
+        let mut backups = vec![
+            "2023-01-02_10-00-00".to_string(),
+            "2022-12-31_23-59-59".to_string(),
+            "2023-01-01_08-30-00".to_string(),
+        ];
+        assert_eq!(
+            backups_to_remove(&mut backups, 1),
+            vec!["2022-12-31_23-59-59", "2023-01-01_08-30-00"]
+        );
+    }
+
+    #[test]
+    fn keep_backups_under_limit() {
+        let mut backups = vec!["2023-01-01_08-30-00".to_string()];
+        assert!(backups_to_remove(&mut backups, 5).is_empty());
+    }
+
+    #[test]
+    fn keep_every_backup_without_limit() {
+        let mut backups = vec![
+            "2023-01-02_10-00-00".to_string(),
+            "2022-12-31_23-59-59".to_string(),
+        ];
+        assert!(backups_to_remove(&mut backups, 0).is_empty());
+    }
+
+    #[test]
+    fn only_remove_backup_folders() {
+        let temp_dir = TempDir::new();
+        let players_folder = format!("{}/players", temp_dir.path());
+        std::fs::create_dir(&players_folder).unwrap();
+        for folder in [".git", "0-archive", "2022-12-31_23-59-59"] {
+            std::fs::create_dir(format!("{}/{folder}", temp_dir.path())).unwrap();
+        }
+        let config = BackupConfig {
+            folder: temp_dir.path().to_string(),
+            interval_minutes: 0,
+            max_backups: 1,
+        };
+        backup_players(&players_folder, &config).unwrap();
+        let exists = |folder: &str| std::path::Path::new(temp_dir.path()).join(folder).exists();
+        assert!(exists(".git"));
+        assert!(exists("0-archive"));
+        assert!(exists("players"));
+        assert!(!exists("2022-12-31_23-59-59"));
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt::Display;
+
+use poise::ChoiceParameter;
+use serde::{Deserialize, Serialize};
+
+/// The difficulty tier of a roll, chosen with the roll or set by the game master
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ChoiceParameter)]
+pub enum Difficulty {
+    Trivial,
+    Easy,
+    Normal,
+    Hard,
+    Heroic,
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// How a difficulty tier changes the threshold to succeed a roll
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyTier {
+    #[serde(default)]
+    pub offset: i32, // Added to the threshold
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64, // Multiplies the threshold, before the offset is added
+}
+
+fn default_multiplier() -> f64 {
+    1.0
+}
+
+impl DifficultyTier {
+    fn offset(offset: i32) -> Self {
+        DifficultyTier {
+            offset,
+            multiplier: 1.0,
+        }
+    }
+
+    /// Get the threshold to succeed a roll of this difficulty
+    pub fn apply(&self, threshold: i32) -> i32 {
+        (threshold as f64 * self.multiplier).round() as i32 + self.offset
+    }
+}
+
+/// The effect of each difficulty tier, a normal roll is unchanged by default
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifficultyConfig {
+    pub trivial: DifficultyTier,
+    pub easy: DifficultyTier,
+    pub normal: DifficultyTier,
+    pub hard: DifficultyTier,
+    pub heroic: DifficultyTier,
+}
+
+impl Default for DifficultyConfig {
+    fn default() -> Self {
+        DifficultyConfig {
+            trivial: DifficultyTier::offset(40),
+            easy: DifficultyTier::offset(20),
+            normal: DifficultyTier::offset(0),
+            hard: DifficultyTier::offset(-20),
+            heroic: DifficultyTier::offset(-40),
+        }
+    }
+}
+
+impl DifficultyConfig {
+    pub fn tier(&self, difficulty: Difficulty) -> &DifficultyTier {
+        match difficulty {
+            Difficulty::Trivial => &self.trivial,
+            Difficulty::Easy => &self.easy,
+            Difficulty::Normal => &self.normal,
+            Difficulty::Hard => &self.hard,
+            Difficulty::Heroic => &self.heroic,
+        }
+    }
+
+    /// Check that no tier turns the threshold upside down
+    pub fn validate(&self) -> Result<(), String> {
+        for difficulty in [
+            Difficulty::Trivial,
+            Difficulty::Easy,
+            Difficulty::Normal,
+            Difficulty::Hard,
+            Difficulty::Heroic,
+        ] {
+            if self.tier(difficulty).multiplier < 0.0 {
+                return Err(format!(
+                    "The multiplier of {difficulty} should not be negative"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Difficulty, DifficultyConfig, DifficultyTier};
+
+    #[test]
+    fn tiers_offset_or_multiply_the_threshold() {
+        let config: DifficultyConfig = serde_json::from_str(
+            r#"{"hard": {"multiplier": 0.5}, "heroic": {"offset": -10, "multiplier": 0.5}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.tier(Difficulty::Trivial).apply(50), 90);
+        assert_eq!(config.tier(Difficulty::Normal).apply(50), 50);
+        assert_eq!(config.tier(Difficulty::Hard).apply(50), 25);
+        assert_eq!(config.tier(Difficulty::Heroic).apply(50), 15);
+    }
+
+    #[test]
+    fn negative_multipliers_are_invalid() {
+        let config = DifficultyConfig {
+            hard: DifficultyTier {
+                offset: 0,
+                multiplier: -1.0,
+            },
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        assert!(DifficultyConfig::default().validate().is_ok());
+    }
+}
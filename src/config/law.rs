@@ -0,0 +1,219 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use rand::distributions::WeightedIndex;
+use rand::Rng;
+use rand_distr::{Beta, Distribution, Normal, Triangular};
+use serde::{Deserialize, Serialize};
+
+use crate::dice::roll_dice;
+use crate::Error;
+
+/// How the 100 faced dice of the roll command is rolled. Every law gives a roll between 1 and 100
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "law", content = "parameters")]
+pub enum StatisticLaw {
+    Uniform,
+    /// Mean and standard deviation
+    Normal(f64, f64),
+    /// Min, mode and max
+    Triangular(f64, f64, f64),
+    /// Alpha and beta, scaled from [0, 1] to [1, 100]
+    Beta(f64, f64),
+    /// The sum of the dice of a dice expression, like "2d50"
+    Dice(String),
+    /// A weight for each roll, read from a file of "<roll> <weight>" lines relative to the config folder
+    Table {
+        file: String,
+        #[serde(skip)]
+        weights: Vec<(i32, f64)>,
+        #[serde(skip)]
+        index: Option<WeightedIndex<f64>>, // Built once from the weights, to pick a roll
+    },
+}
+
+impl StatisticLaw {
+    /// Check that the parameters of the law make sense and read its table if any,
+    /// so that no error happens while rolling
+    pub fn load(&mut self, config_folder: &Path) -> Result<(), Error> {
+        match self {
+            StatisticLaw::Uniform => {}
+            StatisticLaw::Normal(_, std_dev) if *std_dev < 0.0 => {
+                return Err("The standard deviation should not be negative".into());
+            }
+            StatisticLaw::Normal(mean, std_dev) => {
+                Normal::new(*mean, *std_dev)
+                    .map_err(|e| format!("Invalid normal law ({mean}, {std_dev}): {e}"))?;
+            }
+            StatisticLaw::Triangular(min, mode, max) => {
+                Triangular::new(*min, *max, *mode)
+                    .map_err(|e| format!("Invalid triangular law ({min}, {mode}, {max}): {e}"))?;
+            }
+            StatisticLaw::Beta(alpha, beta) => {
+                Beta::new(*alpha, *beta)
+                    .map_err(|e| format!("Invalid beta law ({alpha}, {beta}): {e}"))?;
+            }
+            StatisticLaw::Dice(expression) => {
+                // Rolling once is enough to know if the expression is valid
+                roll_dice(expression, &mut rand::thread_rng())
+                    .map_err(|e| format!("Invalid dice law {expression:?}: {e}"))?;
+            }
+            StatisticLaw::Table {
+                file,
+                weights,
+                index,
+            } => {
+                let path = config_folder.join(&*file);
+                let content = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Could not read file {}: {e}", path.display()))?;
+                *weights = parse_table(&content)
+                    .map_err(|e| format!("Invalid table {}: {e}", path.display()))?;
+                *index = Some(WeightedIndex::new(
+                    weights.iter().map(|(_, weight)| weight),
+                )?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Roll a dice following this law
+    pub fn roll(&self, rng: &mut impl Rng) -> Result<i32, Error> {
+        let roll = match self {
+            StatisticLaw::Uniform => rng.gen_range(1..101),
+            StatisticLaw::Normal(mean, std_dev) => {
+                Normal::new(*mean, *std_dev)?.sample(rng).clamp(1.0, 100.0) as i32
+            }
+            StatisticLaw::Triangular(min, mode, max) => Triangular::new(*min, *max, *mode)?
+                .sample(rng)
+                .clamp(1.0, 100.0) as i32,
+            StatisticLaw::Beta(alpha, beta) => {
+                (1.0 + Beta::new(*alpha, *beta)?.sample(rng) * 99.0).round() as i32
+            }
+            StatisticLaw::Dice(expression) => {
+                roll_dice(expression, rng)?.total.clamp(1, 100) as i32
+            }
+            StatisticLaw::Table { weights, index, .. } => {
+                let index = index
+                    .as_ref()
+                    .ok_or("The table of the law was not loaded")?;
+                weights[index.sample(rng)].0
+            }
+        };
+        Ok(roll)
+    }
+}
+
+impl Display for StatisticLaw {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatisticLaw::Uniform => write!(f, "Uniform"),
+            StatisticLaw::Normal(mean, std_dev) => write!(f, "Normal ({mean}, {std_dev})"),
+            StatisticLaw::Triangular(min, mode, max) => {
+                write!(f, "Triangular ({min}, {mode}, {max})")
+            }
+            StatisticLaw::Beta(alpha, beta) => write!(f, "Beta ({alpha}, {beta})"),
+            StatisticLaw::Dice(expression) => write!(f, "Dice ({expression})"),
+            StatisticLaw::Table { file, .. } => write!(f, "Table ({file})"),
+        }
+    }
+}
+
+/// Parse the weight of each roll, the rolls that are not listed are never rolled
+fn parse_table(content: &str) -> Result<Vec<(i32, f64)>, Error> {
+    let mut weights = vec![];
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let parse = || -> Option<(i32, f64)> {
+            let (roll, weight) = line.split_once(char::is_whitespace)?;
+            Some((roll.parse().ok()?, weight.trim().parse().ok()?))
+        };
+        let (roll, weight) = parse().ok_or(format!("{line:?} should be \"<roll> <weight>\""))?;
+        if !(1..=100).contains(&roll) {
+            return Err(format!("The roll {roll} should be between 1 and 100").into());
+        }
+        if !(weight >= 0.0 && weight.is_finite()) {
+            return Err(format!("The weight of {roll} should be a positive number").into());
+        }
+        if weights.iter().any(|(r, _)| *r == roll) {
+            return Err(format!("The roll {roll} is listed twice").into());
+        }
+        weights.push((roll, weight));
+    }
+    if weights.iter().all(|(_, weight)| *weight == 0.0) {
+        return Err("At least one roll should have a positive weight".into());
+    }
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{parse_table, StatisticLaw};
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn rolls_stay_between_1_and_100() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for mut law in [
+            StatisticLaw::Uniform,
+            StatisticLaw::Normal(50.0, 40.0),
+            StatisticLaw::Triangular(1.0, 20.0, 100.0),
+            StatisticLaw::Beta(0.5, 0.5),
+            StatisticLaw::Dice("2d50".to_string()),
+            StatisticLaw::Dice("3d100-50".to_string()),
+        ] {
+            law.load(Path::new(".")).unwrap();
+            for _ in 0..1000 {
+                let roll = law.roll(&mut rng).unwrap();
+                assert!((1..=100).contains(&roll), "{law:?} rolled {roll}");
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_laws() {
+        for mut law in [
+            StatisticLaw::Normal(50.0, -1.0),
+            StatisticLaw::Triangular(1.0, 120.0, 100.0),
+            StatisticLaw::Beta(0.0, 1.0),
+            StatisticLaw::Dice("2d".to_string()),
+            StatisticLaw::Table {
+                file: "missing.txt".to_string(),
+                weights: vec![],
+                index: None,
+            },
+        ] {
+            assert!(
+                law.load(Path::new(".")).is_err(),
+                "{law:?} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn weighted_table() {
+        let weights = parse_table("1 1\n\n50 0\n100 3\n").unwrap();
+        assert_eq!(weights, vec![(1, 1.0), (50, 0.0), (100, 3.0)]);
+        let temp_dir = TempDir::new();
+        temp_dir.write("table.txt", "1 1\n\n50 0\n100 3\n");
+        let mut law = StatisticLaw::Table {
+            file: "table.txt".to_string(),
+            weights: vec![],
+            index: None,
+        };
+        law.load(Path::new(temp_dir.path())).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let rolls: Vec<i32> = (0..1000).map(|_| law.roll(&mut rng).unwrap()).collect();
+        assert!(rolls.iter().all(|r| *r == 1 || *r == 100));
+        assert!(rolls.iter().filter(|r| **r == 100).count() > 600);
+
+        assert!(parse_table("0 1").is_err());
+        assert!(parse_table("1 -1").is_err());
+        assert!(parse_table("1 1\n1 2").is_err());
+        assert!(parse_table("1 0").is_err());
+        assert!(parse_table("1").is_err());
+    }
+}
@@ -0,0 +1,415 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// How the experience of a player in a stat is turned into a mastery, the threshold to succeed a roll.
+/// Talents and affinities make the player learn faster by increasing the experience given to the curve
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "curve", content = "parameters")]
+pub enum MasteryCurve {
+    /// ceiling - (ceiling - floor) * exp(-xp / learning_constant)
+    Exponential { floor: f64, ceiling: f64 },
+    /// floor + slope * xp, up to cap
+    Linear { floor: f64, slope: f64, cap: f64 },
+    /// floor + scale * ln(1 + xp / learning_constant), up to cap
+    Logarithmic { floor: f64, scale: f64, cap: f64 },
+    /// Linear interpolation between [xp, mastery] breakpoints, constant outside of them
+    Piecewise { points: Vec<[f64; 2]> },
+    /// An arithmetic expression of `xp` and `learning_constant`
+    Expression {
+        formula: String,
+        #[serde(skip)]
+        parsed: Option<Expression>, // Parsed once when the config is loaded
+    },
+}
+
+impl Default for MasteryCurve {
+    fn default() -> Self {
+        MasteryCurve::Exponential {
+            floor: 1.0,
+            ceiling: 100.0,
+        }
+    }
+}
+
+impl MasteryCurve {
+    /// Check that the parameters of the curve make sense and parse its formula if any,
+    /// so that no error happens while rolling
+    pub fn load(&mut self, learning_constant: f64) -> Result<(), Error> {
+        match self {
+            MasteryCurve::Exponential { floor, ceiling } if *floor > *ceiling => {
+                Err(format!("The floor {floor} should not be above the ceiling {ceiling}").into())
+            }
+            MasteryCurve::Linear { floor, cap, .. }
+            | MasteryCurve::Logarithmic { floor, cap, .. }
+                if *floor > *cap =>
+            {
+                Err(format!("The floor {floor} should not be above the cap {cap}").into())
+            }
+            MasteryCurve::Linear { slope, .. } if *slope < 0.0 => {
+                Err("The slope should not be negative".into())
+            }
+            MasteryCurve::Logarithmic { scale, .. } if *scale < 0.0 => {
+                Err("The scale should not be negative".into())
+            }
+            MasteryCurve::Piecewise { points } if points.is_empty() => {
+                Err("At least one breakpoint is needed".into())
+            }
+            MasteryCurve::Piecewise { points } => {
+                for pair in points.windows(2) {
+                    if pair[0][0] >= pair[1][0] {
+                        return Err("The xp of the breakpoints should be increasing".into());
+                    }
+                    if pair[0][1] > pair[1][1] {
+                        return Err("The mastery of the breakpoints should not decrease".into());
+                    }
+                }
+                Ok(())
+            }
+            MasteryCurve::Expression { formula, parsed } => {
+                let expression = Expression::parse(formula)?;
+                let mastery = expression.evaluate(0.0, learning_constant)?;
+                if !mastery.is_finite() {
+                    return Err(format!("{formula:?} does not give a number for 0 xp").into());
+                }
+                *parsed = Some(expression);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the mastery corresponding to the experience
+    pub fn mastery(&self, xp: f64, learning_constant: f64) -> Result<i32, Error> {
+        let mastery = match self {
+            MasteryCurve::Exponential { floor, ceiling } => {
+                ceiling - (ceiling - floor) * f64::exp(-xp / learning_constant)
+            }
+            MasteryCurve::Linear { floor, slope, cap } => (floor + slope * xp).min(*cap),
+            MasteryCurve::Logarithmic { floor, scale, cap } => {
+                (floor + scale * f64::ln(1.0 + xp / learning_constant)).min(*cap)
+            }
+            MasteryCurve::Piecewise { points } => interpolate(points, xp),
+            MasteryCurve::Expression { formula, parsed } => {
+                let expression = parsed
+                    .as_ref()
+                    .ok_or("The formula of the curve was not loaded")?;
+                let mastery = expression.evaluate(xp, learning_constant)?;
+                if !mastery.is_finite() {
+                    return Err(format!("{formula:?} does not give a number for {xp} xp").into());
+                }
+                mastery
+            }
+        };
+        Ok(mastery as i32)
+    }
+}
+
+// Linearly interpolate the mastery between the two breakpoints surrounding the xp
+fn interpolate(points: &[[f64; 2]], xp: f64) -> f64 {
+    match points.iter().position(|[x, _]| *x > xp) {
+        Some(0) => points[0][1],
+        Some(i) => {
+            let [x0, y0] = points[i - 1];
+            let [x1, y1] = points[i];
+            y0 + (y1 - y0) * (xp - x0) / (x1 - x0)
+        }
+        None => points.last().map_or(0.0, |[_, y]| *y),
+    }
+}
+
+/// A parsed arithmetic expression, supporting + - * / ^, parentheses,
+/// the variables `xp` and `learning_constant` and the functions exp, ln, sqrt, min and max
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Expression>),
+    Operation(char, Box<Expression>, Box<Expression>),
+    Function(String, Vec<Expression>),
+}
+
+impl Expression {
+    fn parse(formula: &str) -> Result<Self, Error> {
+        let tokens = tokenize(formula)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let expression = parser.sum()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(format!("Unexpected {token:?} in {formula:?}").into()),
+        }
+    }
+
+    fn evaluate(&self, xp: f64, learning_constant: f64) -> Result<f64, Error> {
+        let evaluate = |e: &Expression| e.evaluate(xp, learning_constant);
+        Ok(match self {
+            Expression::Number(n) => *n,
+            Expression::Variable(name) => match name.as_str() {
+                "xp" => xp,
+                "learning_constant" => learning_constant,
+                _ => return Err(format!("Unknown variable {name:?}").into()),
+            },
+            Expression::Negate(e) => -evaluate(e)?,
+            Expression::Operation(op, left, right) => {
+                let (left, right) = (evaluate(left)?, evaluate(right)?);
+                match op {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    '/' => left / right,
+                    _ => left.powf(right),
+                }
+            }
+            Expression::Function(name, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(evaluate)
+                    .collect::<Result<Vec<f64>, Error>>()?;
+                match (name.as_str(), arguments.as_slice()) {
+                    ("exp", [x]) => x.exp(),
+                    ("ln", [x]) => x.ln(),
+                    ("sqrt", [x]) => x.sqrt(),
+                    ("min", [x, y]) => x.min(*y),
+                    ("max", [x, y]) => x.max(*y),
+                    _ => {
+                        return Err(format!(
+                            "Unknown function {name:?} with {} arguments",
+                            arguments.len()
+                        )
+                        .into())
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Symbol(char),
+}
+
+fn tokenize(formula: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut chars = formula.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                number.push(c);
+                chars.next();
+            }
+            let number = number
+                .parse()
+                .map_err(|_| format!("Invalid number {number:?}"))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut identifier = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                identifier.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Identifier(identifier));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(format!("Unexpected character {c:?} in {formula:?}").into());
+        }
+    }
+    Ok(tokens)
+}
+
+// A recursive descent parser, each method parses an operation of higher priority than the previous one
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), Error> {
+        match self.next() {
+            Some(Token::Symbol(c)) if c == symbol => Ok(()),
+            token => Err(format!("Expected {symbol:?}, found {token:?}").into()),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.product()?;
+        while let Some(Token::Symbol(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.next();
+            expression = Expression::Operation(op, Box::new(expression), Box::new(self.product()?));
+        }
+        Ok(expression)
+    }
+
+    fn product(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.unary()?;
+        while let Some(Token::Symbol(op @ ('*' | '/'))) = self.peek().cloned() {
+            self.next();
+            expression = Expression::Operation(op, Box::new(expression), Box::new(self.unary()?));
+        }
+        Ok(expression)
+    }
+
+    // The minus sign has a lower priority than the power: -2^2 = -(2^2)
+    fn unary(&mut self) -> Result<Expression, Error> {
+        if self.peek() == Some(&Token::Symbol('-')) {
+            self.next();
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expression, Error> {
+        let base = self.primary()?;
+        if self.peek() == Some(&Token::Symbol('^')) {
+            self.next();
+            // The power is right associative: 2^3^2 = 2^(3^2)
+            return Ok(Expression::Operation(
+                '^',
+                Box::new(base),
+                Box::new(self.unary()?),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expression, Error> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expression::Number(n)),
+            Some(Token::Identifier(name)) if self.peek() == Some(&Token::Symbol('(')) => {
+                self.next();
+                let mut arguments = vec![self.sum()?];
+                while self.peek() == Some(&Token::Symbol(',')) {
+                    self.next();
+                    arguments.push(self.sum()?);
+                }
+                self.expect(')')?;
+                Ok(Expression::Function(name, arguments))
+            }
+            Some(Token::Identifier(name)) => Ok(Expression::Variable(name)),
+            Some(Token::Symbol('(')) => {
+                let expression = self.sum()?;
+                self.expect(')')?;
+                Ok(expression)
+            }
+            token => Err(format!("Unexpected {token:?}").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MasteryCurve;
+
+    #[test]
+    fn default_curve_is_the_historical_exponential() {
+        let curve = MasteryCurve::default();
+        for xp in [0.0, 42.0, 669.2, 5000.0] {
+            let expected = (100.0 - 99.0 * f64::exp(-xp / 669.2)) as i32;
+            assert_eq!(curve.mastery(xp, 669.2).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn linear_and_logarithmic_curves_are_capped() {
+        let linear = MasteryCurve::Linear {
+            floor: 5.0,
+            slope: 0.5,
+            cap: 90.0,
+        };
+        assert_eq!(linear.mastery(10.0, 669.2).unwrap(), 10);
+        assert_eq!(linear.mastery(1000.0, 669.2).unwrap(), 90);
+        let logarithmic = MasteryCurve::Logarithmic {
+            floor: 1.0,
+            scale: 20.0,
+            cap: 95.0,
+        };
+        assert_eq!(logarithmic.mastery(0.0, 100.0).unwrap(), 1);
+        assert_eq!(logarithmic.mastery(100.0, 100.0).unwrap(), 14);
+        assert_eq!(logarithmic.mastery(1e9, 100.0).unwrap(), 95);
+    }
+
+    #[test]
+    fn piecewise_curve_interpolates_between_breakpoints() {
+        let curve = MasteryCurve::Piecewise {
+            points: vec![[0.0, 10.0], [100.0, 50.0], [200.0, 60.0]],
+        };
+        assert_eq!(curve.mastery(0.0, 669.2).unwrap(), 10);
+        assert_eq!(curve.mastery(50.0, 669.2).unwrap(), 30);
+        assert_eq!(curve.mastery(150.0, 669.2).unwrap(), 55);
+        assert_eq!(curve.mastery(1000.0, 669.2).unwrap(), 60);
+    }
+
+    fn expression(formula: &str) -> MasteryCurve {
+        MasteryCurve::Expression {
+            formula: formula.to_string(),
+            parsed: None,
+        }
+    }
+
+    #[test]
+    fn expression_curve() {
+        let mut curve =
+            expression("min(10 + 2 * sqrt(xp), 100 - 99 * exp(-xp / learning_constant))");
+        curve.load(669.2).unwrap();
+        assert_eq!(curve.mastery(100.0, 669.2).unwrap(), 14);
+        assert_eq!(curve.mastery(0.0, 669.2).unwrap(), 1);
+        let mut power = expression("-2^2 + 2^3^2 / (1 + 1)");
+        power.load(669.2).unwrap();
+        assert_eq!(power.mastery(0.0, 669.2).unwrap(), 252);
+    }
+
+    #[test]
+    fn expression_curve_without_a_number() {
+        let mut curve = expression("100 / (xp - 10)");
+        curve.load(669.2).unwrap();
+        assert_eq!(curve.mastery(0.0, 669.2).unwrap(), -10);
+        assert!(curve.mastery(10.0, 669.2).is_err());
+        assert!(expression("xp").mastery(0.0, 669.2).is_err());
+    }
+
+    #[test]
+    fn invalid_curves_are_rejected() {
+        let invalid = [
+            MasteryCurve::Exponential {
+                floor: 100.0,
+                ceiling: 1.0,
+            },
+            MasteryCurve::Linear {
+                floor: 1.0,
+                slope: -1.0,
+                cap: 100.0,
+            },
+            MasteryCurve::Piecewise { points: vec![] },
+            MasteryCurve::Piecewise {
+                points: vec![[10.0, 1.0], [5.0, 20.0]],
+            },
+            expression("100 - level"),
+            expression("log(xp)"),
+            expression("(xp + 1"),
+        ];
+        for mut curve in invalid {
+            assert!(curve.load(669.2).is_err(), "{curve:?} should be invalid");
+        }
+        assert!(MasteryCurve::default().load(669.2).is_ok());
+    }
+}
@@ -1,35 +1,217 @@
-use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 use crate::Error;
+use poise::serenity_prelude::{RoleId, User, UserId};
+use serde::{Deserialize, Serialize};
+
+use self::backup::BackupConfig;
+use self::difficulty::DifficultyConfig;
+use self::law::StatisticLaw;
+use self::mastery::MasteryCurve;
+use self::outcome::{Outcome, OutcomeConfig};
+use self::players::write_atomically;
 
 pub mod affinity;
+pub mod backup;
+pub mod difficulty;
+pub mod law;
+pub mod mastery;
+pub mod outcome;
 pub mod parser;
 pub mod players;
 pub mod stat;
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "law", content = "parameters")]
-pub enum StatisticLaw {
-    Uniform,
-    Normal(f64, f64),
-}
+pub mod stat_rules;
 
 /// Corresponds to the customizable config file that can be modified by the user
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default)]
     pub game_master_discord_name: String,
+    #[serde(default)]
+    pub game_master_discord_ids: Vec<UserId>,
+    #[serde(default)]
+    pub game_master_role_id: Option<RoleId>,
     pub experience_earned_after_success: i32,
     pub experience_earned_after_failure: i32,
+    #[serde(default)]
+    pub outcomes: OutcomeConfig,
+    #[serde(default)]
+    pub difficulties: DifficultyConfig,
     pub learning_constant: f64,
+    #[serde(default)]
+    pub mastery_curve: MasteryCurve,
     pub talent_increase_percentage: f64,
     pub major_affinity_increase_percentage: f64,
     pub minor_affinity_increase_percentage: f64,
     pub roll_command_statistic_law: StatisticLaw,
+    #[serde(default = "default_true")]
+    pub secret_rolls_grant_experience: bool,
+    #[serde(default = "default_true")]
+    pub announce_secret_rolls: bool,
+    #[serde(default)]
+    pub backups: BackupConfig,
+    #[serde(default = "default_history_file")]
+    pub history_file: String,
+    #[serde(default)]
+    pub watch_config_files: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_history_file() -> String {
+    "./history/rolls.jsonl".to_string()
+}
+
+/// Resolve a path of the config relative to the config folder, unless it is absolute
+fn relative_to(config_folder: &Path, path: &str) -> String {
+    let path = Path::new(path);
+    config_folder
+        .join(path.strip_prefix(".").unwrap_or(path))
+        .to_string_lossy()
+        .to_string()
 }
 
 impl Config {
     pub fn from(path: &str) -> Result<Self, Error> {
-        let result = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read file {path}: {e}"))?;
+        let mut result: Config = serde_json::from_str(&content)?;
+        if result.game_master_discord_name.is_empty()
+            && result.game_master_discord_ids.is_empty()
+            && result.game_master_role_id.is_none()
+        {
+            return Err(format!(
+                "{path} should contain game_master_discord_ids, a game_master_role_id or a game_master_discord_name"
+            )
+            .into());
+        }
+        if result.learning_constant <= 0.0 {
+            return Err(format!("The learning_constant of {path} should be positive").into());
+        }
+        if result
+            .outcomes
+            .critical_success_threshold_fraction
+            .is_some_and(|f| !(0.0..=1.0).contains(&f))
+        {
+            return Err(format!(
+                "The critical_success_threshold_fraction of {path} should be between 0 and 1"
+            )
+            .into());
+        }
+        result
+            .difficulties
+            .validate()
+            .map_err(|e| format!("The difficulties of {path} are invalid: {e}"))?;
+        result
+            .mastery_curve
+            .load(result.learning_constant)
+            .map_err(|e| format!("The mastery_curve of {path} is invalid: {e}"))?;
+        let config_folder = Path::new(path).parent().unwrap_or(Path::new("."));
+        // Each campaign keeps its own history and backups, next to its config files
+        result.history_file = relative_to(config_folder, &result.history_file);
+        result.backups.folder = relative_to(config_folder, &result.backups.folder);
+        result
+            .roll_command_statistic_law
+            .load(config_folder)
+            .map_err(|e| format!("The roll_command_statistic_law of {path} is invalid: {e}"))?;
         Ok(result)
     }
+
+    /// Get the experience a player earns in a stat after a roll with this outcome
+    pub fn experience_earned(&self, outcome: &Outcome) -> i32 {
+        match outcome {
+            Outcome::CriticalSuccess { .. } => self
+                .outcomes
+                .experience_earned_after_critical_success
+                .unwrap_or(self.experience_earned_after_success),
+            Outcome::Success { .. } => self.experience_earned_after_success,
+            Outcome::Failure { .. } => self.experience_earned_after_failure,
+            Outcome::CriticalFailure { .. } => self
+                .outcomes
+                .experience_earned_after_critical_failure
+                .unwrap_or(self.experience_earned_after_failure),
+        }
+    }
+
+    /// Is this discord user, who has the given roles on the server, a game master?
+    /// The game master is matched by discord pseudo until game master ids are set in the config
+    pub fn is_game_master(&self, user: &User, roles: &[RoleId]) -> bool {
+        if self.game_master_discord_ids.contains(&user.id) {
+            return true;
+        }
+        if self
+            .game_master_role_id
+            .is_some_and(|role| roles.contains(&role))
+        {
+            return true;
+        }
+        self.is_game_master_by_pseudo(user)
+    }
+
+    /// Is this discord user the game master only because of their pseudo, since no game master id is set?
+    pub fn is_game_master_by_pseudo(&self, user: &User) -> bool {
+        self.game_master_discord_ids.is_empty()
+            && !self.game_master_discord_name.is_empty()
+            && user.name == self.game_master_discord_name
+    }
+}
+
+/// Save the id of the game master in the config file, so that they are no longer identified by pseudo
+pub fn save_game_master_id(path: &str, id: UserId) -> Result<(), Error> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("Could not read file {path}: {e}"))?;
+    let mut config: serde_json::Value = serde_json::from_str(&content)?;
+    config["game_master_discord_ids"] = serde_json::to_value([id])?;
+    write_atomically(path, &serde_json::to_string_pretty(&config)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::{RoleId, UserId};
+
+    use super::{save_game_master_id, Config};
+    use crate::test_utils::{config, user, TempDir};
+
+    #[test]
+    fn game_masters_by_id_or_role() {
+        let config =
+            config(r#"{"game_master_discord_ids": ["1", "2"], "game_master_role_id": "10"}"#);
+        assert!(config.is_game_master(&user(1, "Abraracourcix"), &[]));
+        assert!(config.is_game_master(&user(2, "Panoramix"), &[]));
+        assert!(config.is_game_master(&user(3, "Assurancetourix"), &[RoleId::new(10)]));
+        assert!(!config.is_game_master(&user(4, "Obelix"), &[RoleId::new(11)]));
+    }
+
+    #[test]
+    fn game_master_by_pseudo_until_ids_are_set() {
+        let only_name = config(r#"{"game_master_discord_name": "Abraracourcix"}"#);
+        assert!(only_name.is_game_master(&user(1, "Abraracourcix"), &[]));
+        let name_and_ids = config(
+            r#"{"game_master_discord_name": "Abraracourcix", "game_master_discord_ids": ["2"]}"#,
+        );
+        assert!(!name_and_ids.is_game_master(&user(1, "Abraracourcix"), &[]));
+    }
+
+    #[test]
+    fn save_the_id_of_the_game_master() {
+        let temp_dir = TempDir::new();
+        let path = temp_dir.write(
+            "config.json",
+            r#"{"game_master_discord_name": "Abraracourcix", "experience_earned_after_success": 2,
+            "experience_earned_after_failure": 1, "learning_constant": 669.2,
+            "talent_increase_percentage": 0.2, "major_affinity_increase_percentage": 0.1,
+            "minor_affinity_increase_percentage": 0.05,
+            "roll_command_statistic_law": {"law": "Uniform"}}"#,
+        );
+        assert!(Config::from(&path)
+            .unwrap()
+            .is_game_master_by_pseudo(&user(1, "Abraracourcix")));
+        save_game_master_id(&path, UserId::new(1)).unwrap();
+        let config = Config::from(&path).unwrap();
+        assert!(config.is_game_master(&user(1, "Panoramix"), &[]));
+        assert!(!config.is_game_master(&user(2, "Abraracourcix"), &[]));
+        assert_eq!(config.experience_earned_after_success, 2);
+    }
 }
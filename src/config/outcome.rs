@@ -0,0 +1,139 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+/// The outcome of a roll against a threshold, along with the margin by which it succeeded or failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "degree")]
+pub enum Outcome {
+    CriticalSuccess { margin: i32 },
+    Success { margin: i32 },
+    Failure { margin: i32 },
+    CriticalFailure { margin: i32 },
+}
+
+impl Outcome {
+    /// How far the roll was from the threshold, positive for a success and negative for a failure
+    pub fn margin(&self) -> i32 {
+        match self {
+            Outcome::CriticalSuccess { margin }
+            | Outcome::Success { margin }
+            | Outcome::Failure { margin }
+            | Outcome::CriticalFailure { margin } => *margin,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            Outcome::CriticalSuccess { .. } | Outcome::Success { .. }
+        )
+    }
+
+    /// Compare the outcomes of an opposed roll: the best degree of success wins, then the biggest margin
+    pub fn opposed_cmp(&self, other: &Outcome) -> Ordering {
+        (self.degree(), self.margin()).cmp(&(other.degree(), other.margin()))
+    }
+
+    fn degree(&self) -> u8 {
+        match self {
+            Outcome::CriticalSuccess { .. } => 3,
+            Outcome::Success { .. } => 2,
+            Outcome::Failure { .. } => 1,
+            Outcome::CriticalFailure { .. } => 0,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Outcome::CriticalSuccess { .. } => "CRITICAL SUCCESS",
+            Outcome::Success { .. } => "SUCCESS",
+            Outcome::Failure { .. } => "FAILURE",
+            Outcome::CriticalFailure { .. } => "CRITICAL FAILURE",
+        }
+    }
+}
+
+/// Decides when a roll is a critical success or failure, and the experience earned in those cases.
+/// Criticals are disabled by default
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OutcomeConfig {
+    pub critical_success_roll: Option<i32>, // A roll at or below this value is a critical success
+    pub critical_success_threshold_fraction: Option<f64>, // A roll at or below this fraction of the threshold is a critical success
+    pub critical_failure_roll: Option<i32>, // A roll at or above this value is a critical failure
+    pub experience_earned_after_critical_success: Option<i32>, // Same as after a success if not set
+    pub experience_earned_after_critical_failure: Option<i32>, // Same as after a failure if not set
+}
+
+impl OutcomeConfig {
+    /// Get the outcome of a roll, which succeeds if it is at or below the threshold
+    pub fn outcome(&self, roll: i32, threshold: i32) -> Outcome {
+        let margin = threshold - roll;
+        let is_critical_success = self.critical_success_roll.is_some_and(|r| roll <= r)
+            || self
+                .critical_success_threshold_fraction
+                .is_some_and(|f| (roll as f64) <= threshold as f64 * f);
+        let is_critical_failure = self.critical_failure_roll.is_some_and(|r| roll >= r);
+        match margin >= 0 {
+            true if is_critical_success => Outcome::CriticalSuccess { margin },
+            true => Outcome::Success { margin },
+            false if is_critical_failure => Outcome::CriticalFailure { margin },
+            false => Outcome::Failure { margin },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{Outcome, OutcomeConfig};
+
+    #[test]
+    fn no_criticals_by_default() {
+        let config = OutcomeConfig::default();
+        assert_eq!(config.outcome(1, 50), Outcome::Success { margin: 49 });
+        assert_eq!(config.outcome(50, 50), Outcome::Success { margin: 0 });
+        assert_eq!(config.outcome(100, 50), Outcome::Failure { margin: -50 });
+    }
+
+    #[test]
+    fn critical_outcomes() {
+        let config = OutcomeConfig {
+            critical_success_roll: Some(5),
+            critical_success_threshold_fraction: Some(0.1),
+            critical_failure_roll: Some(96),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.outcome(5, 30),
+            Outcome::CriticalSuccess { margin: 25 }
+        );
+        assert_eq!(
+            config.outcome(8, 80),
+            Outcome::CriticalSuccess { margin: 72 }
+        );
+        assert_eq!(config.outcome(9, 80), Outcome::Success { margin: 71 });
+        assert_eq!(
+            config.outcome(96, 50),
+            Outcome::CriticalFailure { margin: -46 }
+        );
+        // A roll can't be a critical failure if it is below the threshold, and the other way around
+        assert_eq!(config.outcome(97, 99), Outcome::Success { margin: 2 });
+        assert_eq!(config.outcome(3, 2), Outcome::Failure { margin: -1 });
+    }
+
+    #[test]
+    fn opposed_outcomes() {
+        let critical = Outcome::CriticalSuccess { margin: 5 };
+        let success = Outcome::Success { margin: 30 };
+        let failure = Outcome::Failure { margin: -2 };
+        assert_eq!(critical.opposed_cmp(&success), Ordering::Greater);
+        assert_eq!(failure.opposed_cmp(&success), Ordering::Less);
+        assert_eq!(
+            success.opposed_cmp(&Outcome::Success { margin: 12 }),
+            Ordering::Greater
+        );
+        assert_eq!(failure.opposed_cmp(&failure), Ordering::Equal);
+    }
+}
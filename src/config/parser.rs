@@ -24,6 +24,16 @@ pub trait TreeStructure {
                 .collect()
         }
     }
+
+    /// Get this node and all of its descendants, families included
+    fn walk(&self) -> Vec<Self>
+    where
+        Self: Sized + Clone,
+    {
+        let mut nodes = vec![self.clone()];
+        nodes.extend(self.get_children().iter().flat_map(|c| c.walk()));
+        nodes
+    }
 }
 
 pub fn clean_input(c: char) -> char {
@@ -39,6 +49,38 @@ pub fn clean_input(c: char) -> char {
     }
 }
 
+/// Clean a whole string so that it can be compared to an id
+pub fn clean_string(input: &str) -> String {
+    input.trim().chars().map(clean_input).collect()
+}
+
+/// Score how well an input matches a candidate, the lower the better.
+/// Both strings are cleaned first so that case and accents are ignored.
+/// Returns None if the input doesn't match the candidate at all.
+pub fn fuzzy_score(candidate: &str, input: &str) -> Option<usize> {
+    let candidate = clean_string(candidate);
+    let input = clean_string(input);
+    if candidate.starts_with(&input) {
+        return Some(0);
+    }
+    if let Some(position) = candidate.find(&input) {
+        return Some(1 + position);
+    }
+    // Every character of the input should appear in order in the candidate
+    let mut gaps = 0;
+    let mut candidate_chars = candidate.chars();
+    for c in input.chars() {
+        loop {
+            match candidate_chars.next() {
+                Some(cc) if cc == c => break,
+                Some(_) => gaps += 1,
+                None => return None,
+            }
+        }
+    }
+    Some(100 + gaps)
+}
+
 struct ParsedLine {
     value: String,
     indent_level: usize,
@@ -92,7 +134,8 @@ fn build_tree<T: TreeStructure>(lines: &[ParsedLine], index: usize) -> Result<T,
 
 /// Get the stat tree from the stats.txt file
 pub fn get_tree<T: TreeStructure + Clone>(path: &str) -> Result<Vec<T>, Error> {
-    let file_content = fs::read_to_string(path).expect("Could not read stats file");
+    let file_content =
+        fs::read_to_string(path).map_err(|e| format!("Could not read file {path}: {e}"))?;
     // A root node is needed to build the Stat tree
     let mut parsed_lines = vec![ParsedLine::root()];
     parsed_lines.append(
@@ -110,7 +153,7 @@ pub fn get_tree<T: TreeStructure + Clone>(path: &str) -> Result<Vec<T>, Error> {
 mod tests {
     use crate::config::stat::Stat;
 
-    use super::{build_tree, ParsedLine, TreeStructure};
+    use super::{build_tree, fuzzy_score, ParsedLine, TreeStructure};
 
     fn assert_vec_eq<T: PartialEq>(vec1: Vec<T>, vec2: Vec<T>) {
         assert!(vec1.iter().zip(vec2).all(|(v1, v2)| *v1 == v2));
@@ -234,4 +277,34 @@ mod tests {
         ];
         assert_vec_eq(result, expected);
     }
+
+    #[test]
+    fn walk_includes_families() {
+        let lines = ["Stat1", "    Stat2", "        Stat3", "Stat4"];
+        let result: Vec<String> = build_tree::<Stat>(&get_parsed_lines(&lines), 0)
+            .unwrap()
+            .sub_stats
+            .iter()
+            .flat_map(|s| s.walk())
+            .map(|s| s.display_name)
+            .collect();
+        assert_eq!(result, vec!["Stat1", "Stat2", "Stat3", "Stat4"]);
+    }
+
+    #[test]
+    fn fuzzy_score_ignores_case_and_accents() {
+        assert_eq!(fuzzy_score("Célérité", "cele"), Some(0));
+        assert_eq!(fuzzy_score("Lame courte", "COURTE"), Some(6));
+        assert_eq!(fuzzy_score("Résistance aux éléments", "rsel"), Some(113));
+        assert_eq!(fuzzy_score("Agilité", "lame"), None);
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_prefixes() {
+        let prefix = fuzzy_score("Lame courte", "lame").unwrap();
+        let substring = fuzzy_score("Arme lame", "lame").unwrap();
+        let subsequence = fuzzy_score("Lutte armée", "lame").unwrap();
+        assert!(prefix < substring);
+        assert!(substring < subsequence);
+    }
 }
@@ -1,5 +1,6 @@
+use poise::serenity_prelude::{User, UserId};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs::read_dir};
+use std::{collections::HashMap, fs::read_dir, path::Path};
 
 use crate::Error;
 
@@ -19,13 +20,23 @@ fn sort_alphabetically<T: Serialize, S: serde::Serializer>(
 #[derive(Serialize)]
 struct SortAlphabetically<T: Serialize>(#[serde(serialize_with = "sort_alphabetically")] T);
 
+/// Write to a temporary file first and rename it, so that a crash never leaves a half written file
+pub fn write_atomically(path: &str, content: &str) -> Result<(), Error> {
+    let temporary_path = format!("{path}.tmp");
+    std::fs::write(&temporary_path, content)?;
+    std::fs::rename(&temporary_path, path)?;
+    Ok(())
+}
+
 /// Describe a player
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Player {
     #[serde(skip)]
     path: String, // The path to the file representing this player
-    pub name: String,                    // The name of the player in the game
-    pub discord_name: String,            // The discord pseudo of the player
+    pub name: String,         // The name of the player in the game
+    pub discord_name: String, // The discord pseudo of the player
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discord_id: Option<UserId>, // The discord user bound to the player, it doesn't change with the pseudo
     pub stats: HashMap<String, i32>, // The stats of the player along with his experience for each of them
     pub affinities: Affinities,      // The affinities of the player
     pub talents: Vec<String>,        // The talents of the player (+20% on exp)
@@ -40,13 +51,54 @@ impl Player {
         Ok(value)
     }
 
+    /// Get the path to the file representing this player
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
     /// Increase the experience of the player in the given stat by the given amount
     pub fn increase_experience(&mut self, exp_to_add: i32, stat_name: &str) -> Result<(), Error> {
-        self.stats
-            .entry(stat_name.to_string())
-            .and_modify(|value| *value += exp_to_add);
-        let to_save = serde_json::to_string_pretty(&SortAlphabetically(&self))?;
-        std::fs::write(&self.path, to_save)?;
+        self.save_with(|player| {
+            player
+                .stats
+                .entry(stat_name.to_string())
+                .and_modify(|value| *value += exp_to_add);
+        })
+    }
+
+    /// Decrease the experience of the player in the given stat by the given amount
+    pub fn decrease_experience(
+        &mut self,
+        exp_to_remove: i32,
+        stat_name: &str,
+    ) -> Result<(), Error> {
+        self.increase_experience(-exp_to_remove, stat_name)
+    }
+
+    /// Bind the player to a discord user, so that the user keeps playing it if they change their pseudo
+    pub fn bind_to(&mut self, user: &User) -> Result<(), Error> {
+        self.save_with(|player| {
+            player.discord_id = Some(user.id);
+            player.discord_name = user.name.to_string();
+        })
+    }
+
+    /// Is the player played by this discord user?
+    /// Players that are not bound to a user id yet are matched by discord pseudo
+    pub fn is_played_by(&self, user: &User) -> bool {
+        match self.discord_id {
+            Some(id) => id == user.id,
+            None => self.discord_name == user.name,
+        }
+    }
+
+    // Change a copy of the player and keep it only once saved, so that the player never differs from its file
+    fn save_with(&mut self, change: impl FnOnce(&mut Player)) -> Result<(), Error> {
+        let mut changed = self.clone();
+        change(&mut changed);
+        let to_save = serde_json::to_string_pretty(&SortAlphabetically(&changed))?;
+        write_atomically(&self.path, &to_save)?;
+        *self = changed;
         Ok(())
     }
 
@@ -74,18 +126,78 @@ impl Player {
     }
 }
 
-/// Parse and get the players from the "players" folder
-pub fn get_players(path: &str) -> Result<HashMap<String, String>, Error> {
-    let player_paths = read_dir(path)?;
+/// Parse and get the players from the "players" folder, indexed by the path to their file
+pub fn get_players(path: &str) -> Result<HashMap<String, Player>, Error> {
+    let mut players = HashMap::new();
+    for entry in read_dir(path)? {
+        let path = entry?.path();
+        // Temporary files left by an interrupted save are not players
+        if !is_player_file(&path) {
+            continue;
+        }
+        let path_str = path
+            .to_str()
+            .ok_or(format!("Invalid player file name {}", path.display()))?;
+        let player = Player::from(path_str)?;
+        players.insert(player.path.to_string(), player);
+    }
+    Ok(players)
+}
+
+/// Only the JSON files of the players folder are player files
+pub fn is_player_file(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|e| e == "json")
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::UserId;
 
-    Ok(player_paths
-        .map(|p| {
-            let path = p.as_ref().unwrap().path();
-            let path_str = path.as_os_str().to_str().unwrap();
-            Player::from(path_str)
-        })
-        .collect::<Result<Vec<Player>, Error>>()?
-        .iter()
-        .map(|p| (p.discord_name.to_string(), p.path.to_string()))
-        .collect())
+    use super::{get_players, Player};
+    use crate::test_utils::{user, TempDir};
+
+    fn player(discord_id: Option<u64>) -> Player {
+        let mut player: Player = serde_json::from_str(
+            r#"{"name": "Asterix", "discord_name": "Obelix", "stats": {},
+            "affinities": {"major": [], "minor": []}, "talents": [], "modifiers": {}}"#,
+        )
+        .unwrap();
+        player.discord_id = discord_id.map(UserId::new);
+        player
+    }
+
+    #[test]
+    fn played_by_pseudo_until_bound() {
+        let player = player(None);
+        assert!(player.is_played_by(&user(1, "Obelix")));
+        assert!(!player.is_played_by(&user(1, "Idefix")));
+    }
+
+    #[test]
+    fn played_by_id_once_bound() {
+        let player = player(Some(1));
+        assert!(player.is_played_by(&user(1, "Idefix")));
+        assert!(!player.is_played_by(&user(2, "Obelix")));
+    }
+
+    #[test]
+    fn temporary_files_are_not_players() {
+        let temp_dir = TempDir::new();
+        let player = temp_dir.player();
+        temp_dir.write("player.json.tmp", "{\"name\": \"Asterix\"");
+        let players = get_players(temp_dir.path()).unwrap();
+        assert_eq!(players.len(), 1);
+        assert_eq!(players[player.path()], player);
+    }
+
+    #[test]
+    fn unchanged_when_not_saved() {
+        let temp_dir = TempDir::new();
+        let mut player = temp_dir.player();
+        drop(temp_dir);
+        let unchanged = player.clone();
+        assert!(player.increase_experience(10, "Agilité").is_err());
+        assert!(player.bind_to(&user(1, "Idefix")).is_err());
+        assert_eq!(player, unchanged);
+    }
 }
@@ -1,13 +1,136 @@
+use std::path::Path;
+
+use poise::serenity_prelude::ButtonStyle;
+use serde::Deserialize;
+
 use crate::Error;
 
-use super::parser::{clean_input, TreeStructure};
+use super::parser::{clean_string, fuzzy_score, get_tree, TreeStructure};
+
+/// The colour of the button of a stat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ButtonColour {
+    Blue,
+    Grey,
+    Green,
+    Red,
+}
+
+impl ButtonColour {
+    pub fn style(&self) -> ButtonStyle {
+        match self {
+            ButtonColour::Blue => ButtonStyle::Primary,
+            ButtonColour::Grey => ButtonStyle::Secondary,
+            ButtonColour::Green => ButtonStyle::Success,
+            ButtonColour::Red => ButtonStyle::Danger,
+        }
+    }
+}
 
 /// Represent a stat tree node
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Stat {
     pub id: String,
     pub display_name: String,
     pub sub_stats: Vec<Stat>,
+    pub description: Option<String>, // Shown when choosing between stats
+    pub emoji: Option<String>,       // Shown on the button of the stat
+    pub colour: Option<ButtonColour>, // Grey for a stat and green for a stat family if not set
+    pub hidden: bool,                // Never shown as a button nor suggested, only found by name
+    pub aliases: Vec<String>,        // Other names the stat can be found with
+}
+
+/// A stat as written in the stats.json file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StatEntry {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    emoji: Option<String>,
+    #[serde(default)]
+    colour: Option<ButtonColour>,
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    stats: Vec<StatEntry>,
+}
+
+impl StatEntry {
+    fn into_stat(self) -> Result<Stat, Error> {
+        if self.stats.len() > 20 {
+            return Err(format!(
+                "There shouldn't be more than 20 stats in {:?}, check your stats.json file.",
+                self.name
+            )
+            .into());
+        }
+        let sub_stats = self
+            .stats
+            .into_iter()
+            .map(StatEntry::into_stat)
+            .collect::<Result<Vec<Stat>, Error>>()?;
+        let mut stat = Stat::from_line(&self.name, &sub_stats)?;
+        if let Some(emoji) = self.emoji.as_deref().filter(|e| !is_emoji(e)) {
+            return Err(format!(
+                "The emoji of {:?} is not a valid emoji: {emoji:?}, check your stats.json file.",
+                self.name
+            )
+            .into());
+        }
+        stat.description = self.description;
+        stat.emoji = self.emoji;
+        stat.colour = self.colour;
+        stat.hidden = self.hidden;
+        stat.aliases = self.aliases;
+        Ok(stat)
+    }
+}
+
+/// Check that the input is a unicode emoji or a discord custom emoji such as <:name:id> or <a:name:id>
+fn is_emoji(input: &str) -> bool {
+    if let Some(custom) = input.strip_prefix('<').and_then(|e| e.strip_suffix('>')) {
+        return match custom.split(':').collect::<Vec<&str>>()[..] {
+            [animated, name, id] => {
+                (animated.is_empty() || animated == "a")
+                    && name.len() >= 2
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    && id.parse::<u64>().is_ok()
+            }
+            _ => false,
+        };
+    }
+    // A unicode emoji is made of a few symbols, joiners and variation selectors, but no letters.
+    // Only keycaps contain ascii characters, and enclosed letters such as 🅰️ are alphabetic
+    let is_emoji_char = |c: char| {
+        matches!(c, '0'..='9' | '#' | '*')
+            || !c.is_ascii() && !c.is_whitespace() && !c.is_alphanumeric()
+            || ('\u{1F100}'..='\u{1F1FF}').contains(&c)
+    };
+    (1..=10).contains(&input.chars().count())
+        && !input.is_ascii()
+        && input.chars().all(is_emoji_char)
+}
+
+/// Get the stat tree of the config folder, from stats.json if it exists or from stats.txt
+pub fn get_stats(config_folder: &str) -> Result<Vec<Stat>, Error> {
+    let path = format!("{config_folder}/stats.json");
+    if !Path::new(&path).exists() {
+        return get_tree(&format!("{config_folder}/stats.txt"));
+    }
+    let content =
+        std::fs::read_to_string(&path).map_err(|e| format!("Could not read file {path}: {e}"))?;
+    let entries: Vec<StatEntry> =
+        serde_json::from_str(&content).map_err(|e| format!("Invalid file {path}: {e}"))?;
+    if entries.len() > 20 {
+        return Err(
+            format!("There shouldn't be more than 20 stats in one category in {path}").into(),
+        );
+    }
+    entries.into_iter().map(StatEntry::into_stat).collect()
 }
 
 impl TreeStructure for Stat {
@@ -26,9 +149,194 @@ impl TreeStructure for Stat {
         }
 
         Ok(Stat {
-            id: raw_line.trim().chars().map(clean_input).collect(),
+            id: clean_string(raw_line),
             display_name: raw_line.trim().to_string(),
             sub_stats: sub_stats.to_vec(),
+            ..Default::default()
         })
     }
 }
+
+impl Stat {
+    /// Find a stat or a stat family in the stat tree from its id, its display name or an alias.
+    /// The input is cleaned, so case and accents don't matter.
+    pub fn find(stats: &[Stat], input: &str) -> Option<Stat> {
+        let id = clean_string(input);
+        let all_stats: Vec<Stat> = stats.iter().flat_map(|s| s.walk()).collect();
+        all_stats
+            .iter()
+            .find(|s| s.id == id || s.display_name == input)
+            .or_else(|| {
+                all_stats
+                    .iter()
+                    .find(|s| s.aliases.iter().any(|a| clean_string(a) == id))
+            })
+            .cloned()
+    }
+
+    /// Get this stat and all of its descendants, unless they are hidden or in a hidden stat family
+    fn walk_visible(&self) -> Vec<Stat> {
+        if self.hidden {
+            return vec![];
+        }
+        let mut stats = vec![self.clone()];
+        stats.extend(self.sub_stats.iter().flat_map(|s| s.walk_visible()));
+        stats
+    }
+
+    /// Get how well the input matches the stat, the lower the better, along with the alias it matched if any
+    pub fn match_score(&self, input: &str) -> Option<(usize, Option<&str>)> {
+        let display_name_score = fuzzy_score(&self.display_name, input).map(|score| (score, None));
+        self.aliases
+            .iter()
+            .filter_map(|a| fuzzy_score(a, input).map(|score| (score, Some(a.as_str()))))
+            .chain(display_name_score)
+            .min_by_key(|(score, alias)| (*score, alias.is_some()))
+    }
+
+    /// Get the stat families leading to the stat with the given id, ending with the stat itself
+    pub fn path_to<'a>(stats: &'a [Stat], id: &str) -> Option<Vec<&'a Stat>> {
+        for stat in stats {
+            if stat.id == id {
+                return Some(vec![stat]);
+            }
+            if let Some(mut path) = Stat::path_to(&stat.sub_stats, id) {
+                path.insert(0, stat);
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    /// Get the stats and stat families matching the input by name or alias, best matches first.
+    /// Hidden stats are never suggested
+    pub fn search(stats: &[Stat], input: &str) -> Vec<Stat> {
+        let mut matches: Vec<(usize, Stat)> = stats
+            .iter()
+            .flat_map(|s| s.walk_visible())
+            .filter_map(|s| Some((s.match_score(input)?.0, s)))
+            .collect();
+        matches.sort_by(|(score1, s1), (score2, s2)| {
+            score1
+                .cmp(score2)
+                .then_with(|| s1.display_name.cmp(&s2.display_name))
+        });
+        matches.into_iter().map(|(_, s)| s).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_stats, is_emoji, ButtonColour, Stat};
+    use crate::test_utils::TempDir;
+
+    /// Write a stats.json file in a temporary folder and parse it
+    fn stats(content: &str) -> Result<Vec<Stat>, crate::Error> {
+        let temp_dir = TempDir::new();
+        temp_dir.write("stats.json", content);
+        get_stats(temp_dir.path())
+    }
+
+    fn combat() -> Vec<Stat> {
+        stats(
+            r#"[{"name": "Combat", "emoji": "⚔️", "colour": "Red", "stats": [
+                {"name": "Poudre noire", "description": "Pistols and muskets",
+                "aliases": ["Arquebuse", "Mousquet"]},
+                {"name": "Botte secrète", "hidden": true}
+            ]}]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_stats_with_metadata() {
+        let stats = combat();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].id, "combat");
+        assert_eq!(stats[0].emoji.as_deref(), Some("⚔️"));
+        assert_eq!(stats[0].colour, Some(ButtonColour::Red));
+        let black_powder = &stats[0].sub_stats[0];
+        assert_eq!(black_powder.id, "poudre_noire");
+        assert_eq!(
+            black_powder.description.as_deref(),
+            Some("Pistols and muskets")
+        );
+        assert!(!black_powder.hidden);
+        assert!(stats[0].sub_stats[1].hidden);
+    }
+
+    #[test]
+    fn find_and_search_by_alias() {
+        let stats = combat();
+        assert_eq!(Stat::find(&stats, "mousquet").unwrap().id, "poudre_noire");
+        let matches = Stat::search(&stats, "arque");
+        assert_eq!(matches[0].id, "poudre_noire");
+        assert_eq!(
+            matches[0].match_score("arque"),
+            Some((0, Some("Arquebuse")))
+        );
+        assert_eq!(matches[0].match_score("poudre"), Some((0, None)));
+    }
+
+    #[test]
+    fn hidden_stats_are_only_found_by_name() {
+        let stats = combat();
+        assert!(Stat::search(&stats, "botte").is_empty());
+        assert_eq!(
+            Stat::find(&stats, "Botte secrète").unwrap().id,
+            "botte_secrete"
+        );
+    }
+
+    #[test]
+    fn invalid_stats() {
+        for content in [
+            r#"[{"name": "Combat", "color": "Red"}]"#,
+            r#"[{"name": "Combat", "colour": "Pink"}]"#,
+            r#"[{"name": "Combat", "emoji": ""}]"#,
+            r#"[{"name": "Combat", "emoji": "abc"}]"#,
+            r#"[{"name": "Combat", "emoji": "épée"}]"#,
+            r#"[{"name": "Combat", "emoji": "<:sword>"}]"#,
+            r#"[{"name": "Combat", "emoji": "<b:sword:123>"}]"#,
+        ] {
+            assert!(stats(content).is_err(), "{content}");
+        }
+    }
+
+    #[test]
+    fn emojis() {
+        for emoji in [
+            "⚔️",
+            "🗡",
+            "1️⃣",
+            "🇫🇷",
+            "🅰️",
+            "👩‍🚀",
+            "<:sword:600404340292059257>",
+            "<a:sword:1>",
+        ] {
+            assert!(is_emoji(emoji), "{emoji}");
+        }
+        for input in ["", "abc", "a⚔️", "⚔️ ⚔️", "é", "1", "<:sword:abc>", "<::1>"] {
+            assert!(!is_emoji(input), "{input}");
+        }
+    }
+
+    #[test]
+    fn errors_name_the_stats_json_file() {
+        let sub_stats = (0..21)
+            .map(|i| format!(r#"{{"name": "Stat {i}"}}"#))
+            .collect::<Vec<String>>()
+            .join(",");
+        let error = stats(&format!(
+            r#"[{{"name": "Combat", "stats": [{sub_stats}]}}]"#
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("stats.json"), "{error}");
+        let error = stats(r#"[{"name": "Combat", "emoji": "abc"}]"#)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("stats.json"), "{error}");
+    }
+}
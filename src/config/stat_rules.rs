@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+use super::law::StatisticLaw;
+use super::stat::Stat;
+use super::Config;
+
+/// Overrides of the config for a stat or a stat family, every field is optional
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatRule {
+    pub statistic_law: Option<StatisticLaw>, // Replaces roll_command_statistic_law
+    pub difficulty: Option<i32>,             // Subtracted from the threshold to succeed a roll
+    pub experience_multiplier: Option<f64>,  // Multiplies the experience earned after a roll
+}
+
+/// The rules of a roll for a stat, once inherited from its families and the config
+#[derive(Debug, Clone, Copy)]
+pub struct RollRules<'a> {
+    pub statistic_law: &'a StatisticLaw,
+    pub difficulty: i32,
+    pub experience_multiplier: f64,
+}
+
+impl<'a> RollRules<'a> {
+    /// The rules of the config, for a roll that is not made for a stat
+    pub fn from_config(config: &'a Config) -> Self {
+        RollRules {
+            statistic_law: &config.roll_command_statistic_law,
+            difficulty: 0,
+            experience_multiplier: 1.0,
+        }
+    }
+
+    /// Apply the experience multiplier to the experience earned after a roll
+    pub fn experience(&self, experience: i32) -> i32 {
+        (experience as f64 * self.experience_multiplier).round() as i32
+    }
+}
+
+/// The rules of each stat or stat family that overrides the config, by stat id.
+/// A stat family passes its rules down to its stats, unless they override them
+#[derive(Debug, Default)]
+pub struct StatRules(HashMap<String, StatRule>);
+
+impl StatRules {
+    /// Parse the stat rules file, which is optional.
+    /// Stats can be referred to by their id or display name
+    pub fn from(path: &str, stats: &[Stat]) -> Result<Self, Error> {
+        if !Path::new(path).exists() {
+            return Ok(StatRules::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read file {path}: {e}"))?;
+        let raw: HashMap<String, StatRule> = serde_json::from_str(&content)?;
+        let config_folder = Path::new(path).parent().unwrap_or(Path::new("."));
+        let mut rules = HashMap::new();
+        for (name, mut rule) in raw {
+            let stat = Stat::find(stats, &name).ok_or(format!(
+                "{path} has rules for {name:?}, which is not a stat"
+            ))?;
+            if let Some(law) = &mut rule.statistic_law {
+                law.load(config_folder)
+                    .map_err(|e| format!("The statistic_law of {name:?} is invalid: {e}"))?;
+            }
+            if rule.experience_multiplier.is_some_and(|m| m < 0.0) {
+                return Err(format!(
+                    "The experience_multiplier of {name:?} should not be negative"
+                )
+                .into());
+            }
+            if rules.insert(stat.id, rule).is_some() {
+                return Err(format!("{path} has rules for {name:?} twice").into());
+            }
+        }
+        Ok(StatRules(rules))
+    }
+
+    /// Get the rules of a roll for this stat, the closest family rules overriding the others
+    pub fn for_stat<'a>(
+        &'a self,
+        stats: &[Stat],
+        stat: &Stat,
+        config: &'a Config,
+    ) -> RollRules<'a> {
+        let mut roll_rules = RollRules::from_config(config);
+        let path = Stat::path_to(stats, &stat.id).unwrap_or_default();
+        for rule in path.iter().filter_map(|s| self.0.get(&s.id)) {
+            if let Some(law) = &rule.statistic_law {
+                roll_rules.statistic_law = law;
+            }
+            if let Some(difficulty) = rule.difficulty {
+                roll_rules.difficulty = difficulty;
+            }
+            if let Some(multiplier) = rule.experience_multiplier {
+                roll_rules.experience_multiplier = multiplier;
+            }
+        }
+        roll_rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StatRules, StatisticLaw};
+    use crate::config::stat::Stat;
+    use crate::test_utils::{config, TempDir};
+
+    fn stat(display_name: &str, id: &str, sub_stats: Vec<Stat>) -> Stat {
+        Stat {
+            id: id.to_string(),
+            display_name: display_name.to_string(),
+            sub_stats,
+            ..Default::default()
+        }
+    }
+
+    fn stats() -> Vec<Stat> {
+        vec![stat(
+            "Combat",
+            "combat",
+            vec![
+                stat("Lame courte", "lamecourte", vec![]),
+                stat("Poudre noire", "poudrenoire", vec![]),
+            ],
+        )]
+    }
+
+    /// Write the rules in a temporary file and parse them
+    fn stat_rules(content: &str) -> Result<StatRules, crate::Error> {
+        let temp_dir = TempDir::new();
+        StatRules::from(&temp_dir.write("stat_rules.json", content), &stats())
+    }
+
+    #[test]
+    fn rules_are_inherited_unless_overridden() {
+        let stat_rules = stat_rules(
+            r#"{"Combat": {"difficulty": 10, "experience_multiplier": 2},
+            "Poudre noire": {"statistic_law": {"law": "Normal", "parameters": [50, 40]},
+            "difficulty": 5}}"#,
+        )
+        .unwrap();
+        let config = config("{}");
+        let stats = stats();
+
+        let short_blade = stat_rules.for_stat(&stats, &stats[0].sub_stats[0], &config);
+        assert_eq!(short_blade.statistic_law, &StatisticLaw::Uniform);
+        assert_eq!(short_blade.difficulty, 10);
+        assert_eq!(short_blade.experience(3), 6);
+
+        let black_powder = stat_rules.for_stat(&stats, &stats[0].sub_stats[1], &config);
+        assert_eq!(
+            black_powder.statistic_law,
+            &StatisticLaw::Normal(50.0, 40.0)
+        );
+        assert_eq!(black_powder.difficulty, 5);
+        assert_eq!(black_powder.experience(3), 6);
+    }
+
+    #[test]
+    fn missing_file_has_no_rules() {
+        let stat_rules = StatRules::from("./no-such-folder/stat_rules.json", &stats()).unwrap();
+        let config = config("{}");
+        let stats = stats();
+        let rules = stat_rules.for_stat(&stats, &stats[0].sub_stats[0], &config);
+        assert_eq!(rules.difficulty, 0);
+        assert_eq!(rules.experience(3), 3);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        for content in [
+            r#"{"Lame longue": {"difficulty": 10}}"#,
+            r#"{"Combat": {}, "combat": {}}"#,
+            r#"{"Combat": {"experience_multiplier": -1}}"#,
+            r#"{"Combat": {"statistic_law": {"law": "Normal", "parameters": [50, -1]}}}"#,
+        ] {
+            assert!(stat_rules(content).is_err(), "{content}");
+        }
+    }
+}
@@ -0,0 +1,507 @@
+use rand::Rng;
+
+use crate::Error;
+
+/// Maximum number of dice in an expression, to keep the breakdown readable
+static MAX_DICE: u32 = 100;
+/// Maximum number of faces of a dice
+static MAX_FACES: u32 = 1000;
+/// Maximum number of times a single exploding dice can explode
+static MAX_EXPLOSIONS: u32 = 100;
+/// Maximum length of an expression, which also bounds the number of operations
+static MAX_LENGTH: usize = 200;
+/// Maximum number of nested parentheses and negations, so that parsing never overflows the stack
+static MAX_DEPTH: u32 = 20;
+/// Maximum length of the breakdown of a group, which fits in a discord embed field
+pub static MAX_BREAKDOWN_LENGTH: usize = 1024;
+
+/// The result of a dice expression, with every dice that was rolled
+#[derive(Debug)]
+pub struct DiceRoll {
+    pub total: i64,
+    pub groups: Vec<RolledGroup>,
+}
+
+/// The dice rolled for one group of the expression, like `3d6kh2`
+#[derive(Debug)]
+pub struct RolledGroup {
+    pub notation: String,
+    pub dice: Vec<RolledDie>,
+}
+
+#[derive(Debug)]
+pub struct RolledDie {
+    pub value: i64,
+    pub kept: bool,     // Dropped dice don't count in the total
+    pub exploded: bool, // The dice rolled its maximum and was rolled again
+}
+
+impl RolledGroup {
+    /// Display every dice of the group, striking through the dropped ones.
+    /// The dice that don't fit in MAX_BREAKDOWN_LENGTH characters are left out
+    pub fn breakdown(&self) -> String {
+        let mut dice: Vec<String> = vec![];
+        let mut length = "[, …]".len();
+        for d in &self.dice {
+            let exploded = if d.exploded { "!" } else { "" };
+            let die = match d.kept {
+                true => format!("{}{exploded}", d.value),
+                false => format!("~~{}{exploded}~~", d.value),
+            };
+            length += die.len() + ", ".len();
+            if length > MAX_BREAKDOWN_LENGTH {
+                dice.push("…".to_string());
+                break;
+            }
+            dice.push(die);
+        }
+        format!("[{}]", dice.join(", "))
+    }
+}
+
+/// Parse a dice expression like `3d6+2`, `2d20kh1`, `4d6dl1`, `d6!`, `2d10r1` or `4dF`
+/// and roll it with the given random number generator
+pub fn roll_dice(expression: &str, rng: &mut impl Rng) -> Result<DiceRoll, Error> {
+    let parsed = DiceExpression::parse(expression)?;
+    let mut groups = vec![];
+    let total = parsed.evaluate(rng, &mut groups)?;
+    Ok(DiceRoll { total, groups })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Faces {
+    Standard(u32),
+    Fudge, // -1, 0 or +1
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Keep {
+    Highest(u32),
+    Lowest(u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DiceGroup {
+    notation: String,
+    count: u32,
+    faces: Faces,
+    keep: Option<Keep>,
+    explode: bool,
+    reroll_at_most: Option<i64>, // Dice at or below this value are rolled again, once
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum DiceExpression {
+    Number(i64),
+    Dice(DiceGroup),
+    Negate(Box<DiceExpression>),
+    Operation(char, Box<DiceExpression>, Box<DiceExpression>),
+}
+
+impl DiceExpression {
+    fn parse(expression: &str) -> Result<Self, Error> {
+        if expression.chars().count() > MAX_LENGTH {
+            return Err(
+                format!("An expression can't be longer than {MAX_LENGTH} characters").into(),
+            );
+        }
+        let mut parser = Parser {
+            chars: expression.chars().collect(),
+            position: 0,
+            dice_count: 0,
+            depth: 0,
+        };
+        let parsed = parser.sum()?;
+        match parser.peek() {
+            None => Ok(parsed),
+            Some(c) => Err(parser.unexpected(c)),
+        }
+    }
+
+    fn evaluate(&self, rng: &mut impl Rng, groups: &mut Vec<RolledGroup>) -> Result<i64, Error> {
+        Ok(match self {
+            DiceExpression::Number(n) => *n,
+            DiceExpression::Dice(group) => {
+                let rolled = group.roll(rng);
+                let total = rolled.dice.iter().filter(|d| d.kept).map(|d| d.value).sum();
+                groups.push(rolled);
+                total
+            }
+            DiceExpression::Negate(e) => -e.evaluate(rng, groups)?,
+            DiceExpression::Operation(op, left, right) => {
+                let left = left.evaluate(rng, groups)?;
+                let right = right.evaluate(rng, groups)?;
+                let result = match op {
+                    '+' => left.checked_add(right),
+                    '-' => left.checked_sub(right),
+                    '*' => left.checked_mul(right),
+                    _ if right == 0 => return Err("Cannot divide by zero".into()),
+                    _ => Some(left.div_euclid(right)),
+                };
+                result.ok_or("The result is too big")?
+            }
+        })
+    }
+}
+
+impl DiceGroup {
+    fn roll_once(&self, rng: &mut impl Rng) -> i64 {
+        match self.faces {
+            Faces::Standard(faces) => rng.gen_range(1..=faces) as i64,
+            Faces::Fudge => rng.gen_range(-1..=1),
+        }
+    }
+
+    fn roll(&self, rng: &mut impl Rng) -> RolledGroup {
+        let mut dice: Vec<RolledDie> = (0..self.count)
+            .map(|_| {
+                let mut value = self.roll_once(rng);
+                if self.reroll_at_most.is_some_and(|r| value <= r) {
+                    value = self.roll_once(rng);
+                }
+                let mut exploded = false;
+                if let (true, Faces::Standard(faces)) = (self.explode, &self.faces) {
+                    let mut last = value;
+                    let mut explosions = 0;
+                    while last == *faces as i64 && explosions < MAX_EXPLOSIONS {
+                        exploded = true;
+                        last = self.roll_once(rng);
+                        value += last;
+                        explosions += 1;
+                    }
+                }
+                RolledDie {
+                    value,
+                    kept: true,
+                    exploded,
+                }
+            })
+            .collect();
+
+        if let Some(keep) = &self.keep {
+            let mut order: Vec<usize> = (0..dice.len()).collect();
+            let (kept, highest_first) = match keep {
+                Keep::Highest(n) => (*n as usize, true),
+                Keep::Lowest(n) => (*n as usize, false),
+            };
+            order.sort_by_key(|&i| dice[i].value);
+            if highest_first {
+                order.reverse();
+            }
+            for &i in &order[kept..] {
+                dice[i].kept = false;
+            }
+        }
+        RolledGroup {
+            notation: self.notation.to_string(),
+            dice,
+        }
+    }
+}
+
+// A recursive descent parser, each method parses an operation of higher priority than the previous one
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    dice_count: u32,
+    depth: u32, // The number of parentheses and negations the parser is currently in
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<char> {
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_whitespace())
+        {
+            self.position += 1;
+        }
+        self.chars.get(self.position).copied()
+    }
+
+    // Unlike peek, whitespaces are not allowed inside a dice notation
+    fn peek_adjacent(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    /// Go one level deeper in the expression, failing if it is nested too deeply
+    fn enter(&mut self) -> Result<(), Error> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(
+                format!("Cannot nest more than {MAX_DEPTH} parentheses or negations").into(),
+            );
+        }
+        Ok(())
+    }
+
+    fn unexpected(&self, c: char) -> Error {
+        format!("Unexpected {c:?} at position {}", self.position + 1).into()
+    }
+
+    fn number(&mut self) -> Result<Option<u32>, Error> {
+        let start = self.position;
+        while self.peek_adjacent().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Ok(None);
+        }
+        let number: String = self.chars[start..self.position].iter().collect();
+        Ok(Some(
+            number.parse().map_err(|_| format!("{number} is too big"))?,
+        ))
+    }
+
+    fn sum(&mut self) -> Result<DiceExpression, Error> {
+        let mut expression = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.position += 1;
+            expression =
+                DiceExpression::Operation(op, Box::new(expression), Box::new(self.product()?));
+        }
+        Ok(expression)
+    }
+
+    fn product(&mut self) -> Result<DiceExpression, Error> {
+        let mut expression = self.unary()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.position += 1;
+            expression =
+                DiceExpression::Operation(op, Box::new(expression), Box::new(self.unary()?));
+        }
+        Ok(expression)
+    }
+
+    fn unary(&mut self) -> Result<DiceExpression, Error> {
+        if self.peek() == Some('-') {
+            self.position += 1;
+            self.enter()?;
+            let expression = DiceExpression::Negate(Box::new(self.unary()?));
+            self.depth -= 1;
+            return Ok(expression);
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<DiceExpression, Error> {
+        match self.peek() {
+            Some('(') => {
+                self.position += 1;
+                self.enter()?;
+                let expression = self.sum()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.position += 1;
+                        self.depth -= 1;
+                        Ok(expression)
+                    }
+                    Some(c) => Err(self.unexpected(c)),
+                    None => Err("A parenthesis is not closed".into()),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == 'd' || c == 'D' => {
+                let start = self.position;
+                let count = self.number()?;
+                match self.peek_adjacent() {
+                    Some('d' | 'D') => self.dice(start, count.unwrap_or(1)),
+                    _ => Ok(DiceExpression::Number(count.unwrap_or(0) as i64)),
+                }
+            }
+            Some(c) => Err(self.unexpected(c)),
+            None => Err("The expression ended unexpectedly".into()),
+        }
+    }
+
+    fn dice(&mut self, start: usize, count: u32) -> Result<DiceExpression, Error> {
+        self.position += 1; // The 'd'
+        let faces = match self.peek_adjacent() {
+            Some('F' | 'f') => {
+                self.position += 1;
+                Faces::Fudge
+            }
+            Some('%') => {
+                self.position += 1;
+                Faces::Standard(100)
+            }
+            _ => match self.number()? {
+                Some(0) => return Err("A dice needs at least one face".into()),
+                Some(faces) if faces > MAX_FACES => {
+                    return Err(format!("A dice can't have more than {MAX_FACES} faces").into())
+                }
+                Some(faces) => Faces::Standard(faces),
+                None => {
+                    return Err(format!(
+                        "Expected a number of faces at position {}",
+                        self.position + 1
+                    )
+                    .into())
+                }
+            },
+        };
+        if count == 0 {
+            return Err("At least one dice should be rolled".into());
+        }
+        self.dice_count = self.dice_count.saturating_add(count);
+        if self.dice_count > MAX_DICE {
+            return Err(format!("Cannot roll more than {MAX_DICE} dice at once").into());
+        }
+
+        let mut group = DiceGroup {
+            notation: String::new(),
+            count,
+            faces,
+            keep: None,
+            explode: false,
+            reroll_at_most: None,
+        };
+        // Parse the modifiers of the dice
+        while let Some(c) = self.peek_adjacent() {
+            match (c, self.chars.get(self.position + 1)) {
+                ('k', Some('h')) | ('k', Some('l')) | ('d', Some('h')) | ('d', Some('l')) => {
+                    let lowest = self.chars[self.position + 1] == 'l';
+                    self.position += 2;
+                    let n = self.number()?.unwrap_or(1);
+                    if n > count {
+                        return Err(format!("Cannot keep or drop {n} dice out of {count}").into());
+                    }
+                    group.keep = Some(match (c, lowest) {
+                        ('k', false) => Keep::Highest(n),
+                        ('k', true) => Keep::Lowest(n),
+                        (_, false) => Keep::Lowest(count - n), // Drop the highest
+                        (_, true) => Keep::Highest(count - n), // Drop the lowest
+                    });
+                }
+                ('k', _) => {
+                    self.position += 1;
+                    let n = self.number()?.unwrap_or(1);
+                    if n > count {
+                        return Err(format!("Cannot keep {n} dice out of {count}").into());
+                    }
+                    group.keep = Some(Keep::Highest(n));
+                }
+                ('!', _) => {
+                    self.position += 1;
+                    match group.faces {
+                        Faces::Standard(1) => return Err("A d1 cannot explode".into()),
+                        Faces::Fudge => return Err("Fudge dice cannot explode".into()),
+                        Faces::Standard(_) => group.explode = true,
+                    }
+                }
+                ('r', _) => {
+                    self.position += 1;
+                    let n = self.number()?.ok_or(format!(
+                        "Expected the value to reroll at position {}",
+                        self.position + 1
+                    ))?;
+                    if let Faces::Standard(faces) = group.faces {
+                        if n >= faces {
+                            return Err(format!(
+                                "Rerolling dice at or below {n} would reroll every d{faces}"
+                            )
+                            .into());
+                        }
+                    }
+                    group.reroll_at_most = Some(n as i64);
+                }
+                _ => break,
+            }
+        }
+        group.notation = self.chars[start..self.position].iter().collect();
+        Ok(DiceExpression::Dice(group))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{roll_dice, MAX_BREAKDOWN_LENGTH};
+
+    fn rolls(expression: &str) -> Vec<super::DiceRoll> {
+        (0..200)
+            .map(|seed| roll_dice(expression, &mut StdRng::seed_from_u64(seed)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(roll_dice("2 + 3 * 4", &mut rng).unwrap().total, 14);
+        assert_eq!(roll_dice("(2 + 3) * 4", &mut rng).unwrap().total, 20);
+        assert_eq!(roll_dice("-7 / 2", &mut rng).unwrap().total, -4);
+    }
+
+    #[test]
+    fn dice_with_modifier() {
+        for roll in rolls("3d6+2") {
+            assert!((5..=20).contains(&roll.total));
+            assert_eq!(roll.groups[0].notation, "3d6");
+            assert_eq!(roll.groups[0].dice.len(), 3);
+        }
+    }
+
+    #[test]
+    fn keep_and_drop() {
+        for roll in rolls("2d20kh1") {
+            let dice = &roll.groups[0].dice;
+            assert_eq!(roll.total, dice.iter().map(|d| d.value).max().unwrap());
+            assert_eq!(dice.iter().filter(|d| d.kept).count(), 1);
+        }
+        for roll in rolls("4d6dl1") {
+            let dice = &roll.groups[0].dice;
+            let sum: i64 = dice.iter().map(|d| d.value).sum();
+            assert_eq!(
+                roll.total,
+                sum - dice.iter().map(|d| d.value).min().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn exploding_rerolled_and_fudge_dice() {
+        let exploding = rolls("d6!");
+        assert!(exploding.iter().any(|r| r.total > 6));
+        assert!(exploding
+            .iter()
+            .all(|r| r.groups[0].dice[0].exploded == (r.total > 6)));
+        assert!(rolls("10d6r1").iter().all(|r| (10..=60).contains(&r.total)));
+        for roll in rolls("4dF") {
+            assert!((-4..=4).contains(&roll.total));
+        }
+    }
+
+    #[test]
+    fn deeply_nested_expressions_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let nested = format!("{}1{}", "(".repeat(100000), ")".repeat(100000));
+        assert!(roll_dice(&nested, &mut rng).is_err());
+        let nested = format!("{}1{}", "(".repeat(20), ")".repeat(20));
+        assert_eq!(roll_dice(&nested, &mut rng).unwrap().total, 1);
+        assert!(roll_dice(&"-".repeat(21), &mut rng).is_err());
+        assert!(roll_dice(&format!("{}1", "-(".repeat(15)), &mut rng).is_err());
+    }
+
+    #[test]
+    fn long_breakdowns_are_truncated() {
+        for roll in rolls("100d2!kh1") {
+            let breakdown = roll.groups[0].breakdown();
+            assert!(breakdown.chars().count() <= MAX_BREAKDOWN_LENGTH);
+        }
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for expression in [
+            "d0", "0d6", "2d", "3d6+", "(1d6", "1d6)", "2d6kh3", "d1!", "dF!", "d6r6", "1/0",
+            "1000d6", "d6 x",
+        ] {
+            assert!(
+                roll_dice(expression, &mut rng).is_err(),
+                "{expression} should be invalid"
+            );
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::commands::roll::RollResult;
+use crate::config::difficulty::Difficulty;
+use crate::config::outcome::Outcome;
+use crate::config::parser::clean_string;
+use crate::config::players::write_atomically;
+use crate::Error;
+
+/// A roll as it is saved in the history file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollRecord {
+    pub id: u64,                    // The number of the roll since the history was created
+    pub timestamp: DateTime<Local>, // When the roll was made
+    pub channel_id: u64,            // The channel in which the roll was made
+    pub message_id: Option<u64>,    // The message displaying the result of the roll
+    pub author: String,             // The discord name of the user who sent the command
+    pub player_name: String,        // The name of the player the roll was made for
+    pub player_discord_name: Option<String>, // The discord name bound to the player file, if any
+    #[serde(default)]
+    pub player_file: Option<String>, // The file of the player the roll was made for, if any
+    pub stat: Option<String>,
+    pub roll: i32,
+    #[serde(default)]
+    pub rolls: Vec<i32>, // Every dice rolled, when rolling with an advantage or disadvantage
+    pub mastery: Option<i32>,
+    pub modifier: Option<i32>,
+    #[serde(default)]
+    pub situational_modifier: Option<i32>,
+    #[serde(default)]
+    pub difficulty: Option<i32>,
+    #[serde(default)]
+    pub difficulty_tier: Option<Difficulty>,
+    pub successful: Option<bool>,
+    #[serde(default)]
+    pub outcome: Option<Outcome>, // Missing in the rolls saved before criticals were introduced
+    pub experience_earned: Option<i32>,
+    pub secret: bool,
+    #[serde(default)]
+    pub voided: bool, // The roll was cancelled by the game master
+}
+
+impl RollRecord {
+    /// The session of a roll is the day it was made on
+    pub fn session(&self) -> String {
+        self.timestamp.format("%Y-%m-%d").to_string()
+    }
+}
+
+/// Criteria to select rolls in the history
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    pub player: Option<String>, // The player name or discord name, for the rolls saved without the player file
+    pub player_file: Option<String>, // The file of the player, that doesn't change with the discord name
+    pub stat: Option<String>,        // The stat display name or id
+    pub session: Option<String>,     // The day of the rolls, as YYYY-MM-DD
+    pub include_secret: bool,        // Whether secret rolls should be listed
+}
+
+impl HistoryFilter {
+    fn matches(&self, record: &RollRecord) -> bool {
+        if record.secret && !self.include_secret {
+            return false;
+        }
+        if self.player.is_some() || self.player_file.is_some() {
+            let is_player = match (&self.player_file, &record.player_file) {
+                (Some(file), Some(record_file)) => file == record_file,
+                _ => self.player.as_ref().is_some_and(|player| {
+                    let player = clean_string(player);
+                    clean_string(&record.player_name) == player
+                        || record
+                            .player_discord_name
+                            .as_ref()
+                            .is_some_and(|d| clean_string(d) == player)
+                }),
+            };
+            if !is_player {
+                return false;
+            }
+        }
+        if let Some(stat) = &self.stat {
+            match &record.stat {
+                Some(s) if clean_string(s) == clean_string(stat) => (),
+                _ => return false,
+            }
+        }
+        if let Some(session) = &self.session {
+            if &record.session() != session {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Log of every roll, stored as one JSON object per line.
+/// Rolls are appended to the file, which is only rewritten when a roll is voided
+#[derive(Debug)]
+pub struct RollHistory {
+    path: String,
+    next_id: Mutex<u64>, // Also makes sure a single roll is written to the file at a time
+}
+
+impl RollHistory {
+    pub fn from(path: &str) -> Result<Self, Error> {
+        let next_id = read_records(path)?
+            .iter()
+            .map(|r| r.id + 1)
+            .max()
+            .unwrap_or(1);
+        Ok(Self {
+            path: path.to_string(),
+            next_id: Mutex::new(next_id),
+        })
+    }
+
+    /// Save a roll at the end of the history file
+    pub async fn record(
+        &self,
+        roll_result: &RollResult,
+        author: &str,
+        channel_id: u64,
+        message_id: Option<u64>,
+    ) -> Result<RollRecord, Error> {
+        let mut next_id = self.next_id.lock().await;
+        let record = RollRecord {
+            id: *next_id,
+            timestamp: Local::now(),
+            channel_id,
+            message_id,
+            author: author.to_string(),
+            player_name: roll_result.player_name.to_string(),
+            player_discord_name: roll_result.player_discord_name.clone(),
+            player_file: roll_result.player_file.clone(),
+            stat: roll_result.stat.clone(),
+            roll: roll_result.roll,
+            rolls: roll_result.rolls.clone(),
+            mastery: roll_result.mastery,
+            modifier: roll_result.modifier,
+            situational_modifier: roll_result.situational_modifier,
+            difficulty: roll_result.difficulty,
+            difficulty_tier: roll_result.difficulty_tier,
+            successful: roll_result.outcome.map(|o| o.is_success()),
+            outcome: roll_result.outcome,
+            experience_earned: roll_result.experience_earned,
+            secret: roll_result.secret,
+            voided: false,
+        };
+        if let Some(folder) = std::path::Path::new(&self.path).parent() {
+            std::fs::create_dir_all(folder)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+        // A line cut by a crash must not swallow the next record
+        if file.seek(SeekFrom::End(0))? > 0 {
+            let mut last_byte = [0];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last_byte)?;
+            if last_byte[0] != b'\n' {
+                writeln!(file)?;
+            }
+        }
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        *next_id += 1;
+        Ok(record)
+    }
+
+    /// Get the last rolls matching the filter, from the oldest to the most recent
+    pub async fn last(
+        &self,
+        count: usize,
+        filter: &HistoryFilter,
+    ) -> Result<Vec<RollRecord>, Error> {
+        // Wait for the roll being written, if any
+        let _lock = self.next_id.lock().await;
+        let records: Vec<RollRecord> = read_records(&self.path)?
+            .into_iter()
+            .filter(|r| filter.matches(r))
+            .collect();
+        let skipped = records.len().saturating_sub(count);
+        Ok(records.into_iter().skip(skipped).collect())
+    }
+
+    /// Mark a roll as voided, either the one with the given id or the last one that isn't voided yet
+    pub async fn void(&self, id: Option<u64>) -> Result<RollRecord, Error> {
+        let _lock = self.next_id.lock().await;
+        let mut records = read_records(&self.path)?;
+        let record = match id {
+            Some(id) => records
+                .iter_mut()
+                .find(|r| r.id == id)
+                .ok_or(format!("Roll #{id} not found"))?,
+            None => records
+                .iter_mut()
+                .rev()
+                .find(|r| !r.voided)
+                .ok_or("No roll to cancel")?,
+        };
+        if record.voided {
+            return Err(format!("Roll #{} was already cancelled", record.id).into());
+        }
+        record.voided = true;
+        let voided_record = record.clone();
+        self.rewrite(&records)?;
+        Ok(voided_record)
+    }
+
+    /// Mark a voided roll as valid again, when cancelling it went wrong
+    pub async fn restore(&self, id: u64) -> Result<(), Error> {
+        let _lock = self.next_id.lock().await;
+        let mut records = read_records(&self.path)?;
+        records
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or(format!("Roll #{id} not found"))?
+            .voided = false;
+        self.rewrite(&records)
+    }
+
+    fn rewrite(&self, records: &[RollRecord]) -> Result<(), Error> {
+        let content = records
+            .iter()
+            .map(|r| Ok(serde_json::to_string(r)? + "\n"))
+            .collect::<Result<String, Error>>()?;
+        write_atomically(&self.path, &content)
+    }
+}
+
+fn read_records(path: &str) -> Result<Vec<RollRecord>, Error> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    // A line cut by a crash is skipped, so that the rest of the history stays readable
+    Ok(content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                warn!(
+                    "Skipping line {} of {path}, which is not a valid roll: {e}",
+                    i + 1
+                );
+                None
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use super::{read_records, HistoryFilter, RollHistory, RollRecord};
+    use crate::test_utils::TempDir;
+
+    fn record(player_name: &str, stat: Option<&str>, secret: bool) -> RollRecord {
+        RollRecord {
+            id: 1,
+            timestamp: Local.with_ymd_and_hms(2023, 5, 14, 20, 30, 0).unwrap(),
+            channel_id: 0,
+            message_id: None,
+            author: "Obelix".to_string(),
+            player_name: player_name.to_string(),
+            player_discord_name: Some("Obelix".to_string()),
+            player_file: None,
+            stat: stat.map(|s| s.to_string()),
+            roll: 42,
+            rolls: vec![42],
+            mastery: Some(50),
+            modifier: Some(0),
+            situational_modifier: None,
+            difficulty: None,
+            difficulty_tier: None,
+            successful: Some(true),
+            outcome: None,
+            experience_earned: Some(2),
+            secret,
+            voided: false,
+        }
+    }
+
+    #[test]
+    fn filter_by_player_and_stat() {
+        let filter = HistoryFilter {
+            player: Some("asterix".to_string()),
+            stat: Some("agilite".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&record("Asterix", Some("Agilité"), false)));
+        assert!(!filter.matches(&record("Asterix", Some("Alchimie"), false)));
+        assert!(!filter.matches(&record("Idefix", Some("Agilité"), false)));
+        assert!(!filter.matches(&record("Asterix", None, false)));
+    }
+
+    #[test]
+    fn filter_by_player_file() {
+        let filter = HistoryFilter {
+            player: Some("Asterix".to_string()),
+            player_file: Some("config/players/asterix.json".to_string()),
+            ..Default::default()
+        };
+        // The discord name of the player changed since the roll
+        let mut renamed = record("Asterix", None, false);
+        renamed.player_discord_name = Some("Astérix le Gaulois".to_string());
+        renamed.player_file = Some("config/players/asterix.json".to_string());
+        assert!(filter.matches(&renamed));
+        // Another player with the same name
+        let mut other = record("Asterix", None, false);
+        other.player_file = Some("config/players/npc.json".to_string());
+        assert!(!filter.matches(&other));
+        // Older rolls are matched by name
+        assert!(filter.matches(&record("Asterix", None, false)));
+    }
+
+    #[test]
+    fn filter_by_session() {
+        let mut filter = HistoryFilter {
+            session: Some("2023-05-14".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&record("Asterix", None, false)));
+        filter.session = Some("2023-05-15".to_string());
+        assert!(!filter.matches(&record("Asterix", None, false)));
+    }
+
+    #[test]
+    fn hide_secret_rolls() {
+        let mut filter = HistoryFilter::default();
+        assert!(!filter.matches(&record("Asterix", None, true)));
+        filter.include_secret = true;
+        assert!(filter.matches(&record("Asterix", None, true)));
+    }
+
+    #[test]
+    fn skip_lines_cut_by_a_crash() {
+        let mut first = record("Asterix", None, false);
+        first.id = 7;
+        let mut second = record("Obelix", None, false);
+        second.id = 8;
+        let temp_dir = TempDir::new();
+        let path = temp_dir.write(
+            "rolls.jsonl",
+            &format!(
+                "{}\n{{\"id\": 9, \"timest\n{}\n",
+                serde_json::to_string(&first).unwrap(),
+                serde_json::to_string(&second).unwrap()
+            ),
+        );
+        let records = read_records(&path).unwrap();
+        assert_eq!(records.iter().map(|r| r.id).collect::<Vec<u64>>(), [7, 8]);
+        let history = RollHistory::from(&path).unwrap();
+        assert_eq!(*history.next_id.try_lock().unwrap(), 9);
+    }
+
+    #[test]
+    fn restore_a_voided_roll() {
+        let temp_dir = TempDir::new();
+        let path = temp_dir.write(
+            "rolls.jsonl",
+            &(serde_json::to_string(&record("Asterix", None, false)).unwrap() + "\n"),
+        );
+        let history = RollHistory::from(&path).unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            assert_eq!(history.void(None).await.unwrap().id, 1);
+            assert!(history.void(None).await.is_err());
+            history.restore(1).await.unwrap();
+            assert!(!read_records(&path).unwrap()[0].voided);
+            assert_eq!(history.void(Some(1)).await.unwrap().id, 1);
+        });
+    }
+}
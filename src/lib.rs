@@ -1,118 +1,177 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
-use config::players::get_players;
-use config::stat::Stat;
-use config::Config;
-use config::{affinity::Affinity, parser::TreeStructure};
+use poise::serenity_prelude::GuildId;
+use serde::Deserialize;
 use tracing::info;
 
-use crate::config::parser::get_tree;
-use crate::config::players::Player;
+pub use crate::campaign::validate_config_files;
+use crate::campaign::Campaign;
+use crate::config::Config;
+use crate::rng::RollRng;
 
+mod campaign;
 pub mod commands;
 mod config;
+pub mod dice;
+mod history;
+pub mod rng;
+#[cfg(test)]
+mod test_utils;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Context<'a> = poise::Context<'a, State, Error>;
 
-/// Holds the configuration, list of stats, and player infos at all time
+/// Maps discord servers to the folder containing the config files of their campaign
+#[derive(Debug, Deserialize)]
+struct CampaignsFile {
+    default: Option<String>, // The campaign of the servers that are not listed
+    guilds: HashMap<GuildId, String>, // The campaign of each server, by server id
+}
+
+impl CampaignsFile {
+    fn from(path: &str) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read file {path}: {e}"))?;
+        let campaigns_file: CampaignsFile = serde_json::from_str(&content)?;
+        campaigns_file.check_distinct_files()?;
+        Ok(campaigns_file)
+    }
+
+    fn config_folders(&self) -> impl Iterator<Item = &String> {
+        self.guilds.values().chain(self.default.iter())
+    }
+
+    /// Check that no two campaigns share their config folder, history file or backups folder,
+    /// since each campaign locks its players and numbers its rolls on its own
+    fn check_distinct_files(&self) -> Result<(), Error> {
+        let mut used: HashMap<PathBuf, String> = HashMap::new();
+        for config_folder in self.config_folders() {
+            let config = Config::from(&format!("{config_folder}/config.json"))?;
+            for (kind, path) in [
+                ("config folder", config_folder.as_str()),
+                ("history file", &config.history_file),
+                ("backups folder", &config.backups.folder),
+            ] {
+                let absolute = std::path::absolute(path)?;
+                if let Some(other) = used.insert(absolute, config_folder.to_string()) {
+                    return Err(format!(
+                        "The campaigns {other} and {config_folder} share the {kind} {path}"
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Holds the campaign of each discord server at all time
 #[derive(Debug)]
 pub struct State {
-    pub config: Config,               // A global config
-    stats: Vec<Stat>,                 // The stat tree that will be used to select a stat
-    affinities: Vec<Affinity>,        // The available affinities groups
-    players: HashMap<String, String>, // The mapping of a discord name with a player file name
+    campaigns: HashMap<GuildId, Campaign>, // The campaign of each server
+    default_campaign: Option<Campaign>,    // The campaign of the servers that are not listed
+    pub rng: RollRng,                      // The source of randomness of every roll
 }
 
 impl State {
-    pub fn from_config_files() -> Result<Self, Error> {
-        let config_folder = "./config";
-        info!("Loading config from {config_folder}");
-        let config = Config::from(&format!("{config_folder}/config.json"))?;
-        let stats = get_tree(&format!("{config_folder}/stats.txt"))?;
-        let affinities = get_tree(&format!("{config_folder}/affinities.txt"))?;
-        let players = get_players(&format!("{config_folder}/players"))?;
-
-        check_validity(&stats, &affinities, &players)?;
-        info!("Config files are correct");
-
+    /// Use the same campaign for every server
+    pub fn from_config_files(config_folder: &str) -> Result<Self, Error> {
         Ok(State {
-            config,
-            stats,
-            affinities,
-            players,
+            campaigns: HashMap::new(),
+            default_campaign: Some(Campaign::from_config_files(config_folder)?),
+            rng: RollRng::from_entropy(),
         })
     }
-}
 
-fn check_validity(
-    stats: &[Stat],
-    affinities: &[Affinity],
-    players: &HashMap<String, String>,
-) -> Result<(), Error> {
-    info!("Checking config files coherence...");
-    // Create a flat vec of stats
-    let flat_stats: Vec<Stat> = stats.iter().flat_map(|s| s.flatten()).collect();
-
-    // Check validity of affinities
-    let flat_affinities: Vec<Affinity> = affinities.iter().flat_map(|a| a.flatten()).collect();
-    for affinity in flat_affinities {
-        if !flat_stats.iter().any(|s| s.id == affinity.id) {
-            return Err(format!(
-                "Affinity stat {:?} is not in stat file",
-                affinity.display_name,
-            )
-            .into());
+    /// Use the campaigns described in the campaigns file
+    pub fn from_campaigns_file(path: &str) -> Result<Self, Error> {
+        let campaigns_file = CampaignsFile::from(path)?;
+        let mut campaigns = HashMap::new();
+        for (guild_id, config_folder) in campaigns_file.guilds {
+            info!("Loading campaign of server {guild_id}");
+            campaigns.insert(guild_id, Campaign::from_config_files(&config_folder)?);
         }
+        let default_campaign = match campaigns_file.default {
+            Some(config_folder) => Some(Campaign::from_config_files(&config_folder)?),
+            None => None,
+        };
+        Ok(State {
+            campaigns,
+            default_campaign,
+            rng: RollRng::from_entropy(),
+        })
     }
 
-    // Check validity of each player
-    for file_path in players.values() {
-        let player = Player::from(file_path)?;
-        for stat in player.stats.keys() {
-            if !flat_stats.iter().any(|s| &s.display_name == stat) {
-                return Err(format!(
-                    "Stat {:?} from file {} is not in stat file",
-                    stat, file_path
-                )
-                .into());
-            }
-        }
-        for stat in &flat_stats {
-            if !player.stats.iter().any(|(s, _)| s == &stat.display_name) {
-                return Err(
-                    format!("Stat {:?} is not in file {}", stat.display_name, file_path).into(),
-                );
-            }
-        }
-        for major_affinity in player.affinities.major {
-            if !affinities.iter().any(|a| a.display_name == major_affinity) {
-                return Err(format!(
-                    "Major affinity {:?} from file {} is not in stat file",
-                    major_affinity, file_path
-                )
-                .into());
-            }
-        }
-        for minor_affinity in player.affinities.minor {
-            if !affinities.iter().any(|a| a.display_name == minor_affinity) {
-                return Err(format!(
-                    "Minor affinity {:?} from file {} is not in stat file",
-                    minor_affinity, file_path
-                )
-                .into());
-            }
+    /// Make the rolls reproducible, by seeding the random number generator
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = RollRng::seeded(seed);
+    }
+
+    /// Get the campaign played on the given server
+    pub fn campaign(&self, guild_id: Option<GuildId>) -> Option<&Campaign> {
+        guild_id
+            .and_then(|id| self.campaigns.get(&id))
+            .or(self.default_campaign.as_ref())
+    }
+
+    fn all_campaigns(&self) -> impl Iterator<Item = &Campaign> {
+        self.campaigns.values().chain(self.default_campaign.iter())
+    }
+
+    /// Periodically back up the player files of every campaign while the bot is running
+    pub fn spawn_backup_tasks(&self) {
+        for campaign in self.all_campaigns() {
+            campaign.spawn_backup_task();
         }
-        for talent in player.talents {
-            if !flat_stats.iter().any(|s| s.display_name == talent) {
-                return Err(format!(
-                    "Talent {:?} from file {} is not in stat file",
-                    talent, file_path
-                )
-                .into());
-            }
+    }
+
+    /// Reload the config files of the campaigns whenever they are modified, if enabled in their config
+    pub fn spawn_config_watchers(&self) -> Result<(), Error> {
+        for campaign in self.all_campaigns() {
+            campaign.spawn_config_watcher()?;
         }
+        Ok(())
     }
+}
 
+/// Check that the config files of every campaign of the campaigns file are valid
+pub fn validate_campaigns_file(path: &str) -> Result<(), Error> {
+    let campaigns_file = CampaignsFile::from(path)?;
+    for config_folder in campaigns_file.config_folders() {
+        validate_config_files(config_folder)
+            .map_err(|e| format!("Campaign {config_folder} is invalid: {e}"))?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::GuildId;
+
+    use super::CampaignsFile;
+
+    #[test]
+    fn parse_campaigns_file() {
+        let campaigns_file: CampaignsFile = serde_json::from_str(
+            r#"{"default": "./config", "guilds": {"123456789": "./pirates"}}"#,
+        )
+        .unwrap();
+        assert_eq!(campaigns_file.default.as_deref(), Some("./config"));
+        assert_eq!(
+            campaigns_file.guilds.get(&GuildId::new(123456789)).unwrap(),
+            "./pirates"
+        );
+    }
+
+    #[test]
+    fn campaigns_cannot_share_their_files() {
+        let campaigns_file: CampaignsFile =
+            serde_json::from_str(r#"{"default": "./config", "guilds": {"123456789": "config"}}"#)
+                .unwrap();
+        assert!(campaigns_file.check_distinct_files().is_err());
+        let campaigns_file: CampaignsFile =
+            serde_json::from_str(r#"{"guilds": {"123456789": "./config"}}"#).unwrap();
+        assert!(campaigns_file.check_distinct_files().is_ok());
+    }
+}
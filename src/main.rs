@@ -1,17 +1,58 @@
 use std::env;
 
+use clap::{Parser, Subcommand};
 use poise::samples::register_in_guild;
 use poise::serenity_prelude::{Client, GatewayIntents};
 use poise::{Framework, FrameworkOptions};
+use rp_tool::commands::claim::claim;
 use rp_tool::commands::dice::dice;
+use rp_tool::commands::dice_stats::dice_stats;
+use rp_tool::commands::difficulty::difficulty;
+use rp_tool::commands::gmroll::gmroll;
+use rp_tool::commands::groupcheck::groupcheck;
+use rp_tool::commands::history::history;
+use rp_tool::commands::opposed::opposed;
 use rp_tool::commands::ping::ping;
+use rp_tool::commands::reload::reload;
 use rp_tool::commands::roll::roll;
 use rp_tool::commands::summary::summary;
-use rp_tool::State;
-use tracing::{error, info};
+use rp_tool::commands::undo::undo;
+use rp_tool::{validate_campaigns_file, validate_config_files, State};
+use tracing::{error, info, warn, Level};
 
 use rp_tool::Error;
 
+/// A discord bot that allows for a better role playing experience
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// The folder containing the config files
+    #[arg(long, global = true, default_value = "./config")]
+    config_dir: String,
+    /// A file mapping discord servers to their config folder, to play one campaign per server
+    #[arg(long, global = true, conflicts_with = "config_dir")]
+    campaigns: Option<String>,
+    /// A file containing only the discord token, used instead of the .env file
+    #[arg(long, global = true)]
+    token_file: Option<String>,
+    /// The most verbose level of logs to display (error, warn, info, debug or trace)
+    #[arg(long, global = true, default_value = "info")]
+    log_level: Level,
+    /// Seed the random number generator, to make the same rolls each time the bot is started
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Start the bot, this is the default
+    Serve,
+    /// Check the config files and exit
+    Validate,
+}
+
 async fn on_error(error: poise::FrameworkError<'_, State, Error>) {
     // They are many errors that can occur, so we only handle the ones we want to customize
     // and forward the rest to the default handler
@@ -30,6 +71,56 @@ async fn on_error(error: poise::FrameworkError<'_, State, Error>) {
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+
+    let subscriber = tracing_subscriber::FmtSubscriber::builder()
+        .with_max_level(cli.log_level)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber)
+        .unwrap_or_else(|e| panic!("Unable to set global default subscriber: {e}"));
+
+    match cli.command {
+        None | Some(Command::Serve) => serve(&cli).await,
+        Some(Command::Validate) => validate(&cli),
+    }
+}
+
+fn validate(cli: &Cli) {
+    let (result, checked) = match &cli.campaigns {
+        Some(path) => (validate_campaigns_file(path), path),
+        None => (validate_config_files(&cli.config_dir), &cli.config_dir),
+    };
+    match result {
+        Ok(()) => println!("The config files in {checked} are valid."),
+        Err(e) => {
+            println!("The config files in {checked} are invalid: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Get the discord token from the given file, or from a .env file
+fn get_token(token_file: Option<&str>) -> String {
+    match token_file {
+        Some(path) => {
+            let token = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                panic!("Could not read the discord token from {path}: {e}");
+            });
+            info!("Found discord token in {path}");
+            token.trim().to_string()
+        }
+        None => {
+            dotenv::dotenv().ok();
+            let token = env::var("DISCORD_TOKEN").unwrap_or_else(|e| {
+                panic!("Expected a discord token in the .env file: {e}");
+            });
+            info!("Found discord token in .env file");
+            token
+        }
+    }
+}
+
+async fn serve(cli: &Cli) {
     // Setup panic hook
     std::panic::set_hook(Box::new(|panic_info| {
         error!("{panic_info}");
@@ -37,10 +128,6 @@ async fn main() {
         std::io::stdin().read_line(&mut String::new()).unwrap();
     }));
 
-    let subscriber = tracing_subscriber::FmtSubscriber::new();
-    tracing::subscriber::set_global_default(subscriber)
-        .unwrap_or_else(|e| panic!("Unable to set global default subscriber: {e}"));
-
     // Setup tracing
     #[cfg(target_os = "windows")]
     {
@@ -52,25 +139,46 @@ async fn main() {
         }
     }
 
-    // Get the discord token from a .env file
-    dotenv::dotenv().ok();
-    let token = env::var("DISCORD_TOKEN").unwrap_or_else(|e| {
-        panic!("Expected a discord token in the .env file: {e}");
-    });
-    info!("Found discord token in .env file");
+    let token = get_token(cli.token_file.as_deref());
 
     // Parse the config files and save them
-    let state = match State::from_config_files() {
+    let state = match &cli.campaigns {
+        Some(path) => State::from_campaigns_file(path),
+        None => State::from_config_files(&cli.config_dir),
+    };
+    let mut state = match state {
         Ok(s) => s,
         Err(e) => {
             panic!("An error occurred while parsing your config files: {e}");
         }
     };
     info!("Config files loaded successfully");
+    if let Some(seed) = cli.seed {
+        warn!("Rolls are seeded with {seed}, they are not random anymore");
+        state.seed_rng(seed);
+    }
+    state.spawn_backup_tasks();
+    if let Err(e) = state.spawn_config_watchers() {
+        error!("Could not watch the config files: {e}");
+    }
 
     let framework = Framework::builder()
         .options(FrameworkOptions {
-            commands: vec![ping(), roll(), summary(), dice()],
+            commands: vec![
+                ping(),
+                roll(),
+                gmroll(),
+                summary(),
+                dice(),
+                history(),
+                undo(),
+                reload(),
+                claim(),
+                opposed(),
+                groupcheck(),
+                dice_stats(),
+                difficulty(),
+            ],
             on_error: |error| Box::pin(on_error(error)),
             ..Default::default()
         })
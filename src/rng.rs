@@ -0,0 +1,39 @@
+use std::sync::Mutex;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// The source of randomness shared by every roll.
+/// It can be seeded, so that the same rolls are made each time the bot is started with this seed
+#[derive(Debug)]
+pub struct RollRng {
+    rng: Mutex<StdRng>,
+}
+
+impl RollRng {
+    /// Unpredictable rolls, for a real game
+    pub fn from_entropy() -> Self {
+        RollRng {
+            rng: Mutex::new(StdRng::from_entropy()),
+        }
+    }
+
+    /// Reproducible rolls, for testing
+    pub fn seeded(seed: u64) -> Self {
+        RollRng {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    /// Use the random number generator, rolls are made one at a time
+    pub fn with<T>(&self, roll: impl FnOnce(&mut StdRng) -> T) -> T {
+        // A panic while rolling doesn't leave the generator in an invalid state
+        let mut rng = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+        roll(&mut rng)
+    }
+
+    /// Get a generator of its own, seeded by the shared one, for long simulations that shouldn't block the other rolls
+    pub fn fork(&self) -> StdRng {
+        self.with(|rng| StdRng::seed_from_u64(rng.gen()))
+    }
+}
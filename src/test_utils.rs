@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use poise::serenity_prelude::{User, UserId};
+
+use crate::config::players::Player;
+use crate::config::Config;
+
+static NEXT_TEMP_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A config with every required field, the given JSON object overriding some of them
+pub fn config(overrides: &str) -> Config {
+    let mut config: serde_json::Value = serde_json::from_str(
+        r#"{"game_master_discord_name": "Abraracourcix", "experience_earned_after_success": 2,
+        "experience_earned_after_failure": 1, "learning_constant": 669.2,
+        "talent_increase_percentage": 0.2, "major_affinity_increase_percentage": 0.1,
+        "minor_affinity_increase_percentage": 0.05,
+        "roll_command_statistic_law": {"law": "Uniform"}}"#,
+    )
+    .unwrap();
+    let overrides: serde_json::Value = serde_json::from_str(overrides).unwrap();
+    for (key, value) in overrides.as_object().unwrap() {
+        config[key] = value.clone();
+    }
+    serde_json::from_value(config).unwrap()
+}
+
+/// A discord user with this id and pseudo
+pub fn user(id: u64, name: &str) -> User {
+    let mut user = User::default();
+    user.id = UserId::new(id);
+    user.name = name.to_string();
+    user
+}
+
+/// A folder of its own in the temporary directory, deleted when dropped,
+/// so that tests running in parallel never write to the same files
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "rp-tool-{}-{}",
+            std::process::id(),
+            NEXT_TEMP_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+
+    /// Write a file in the folder and get its path
+    pub fn write(&self, file_name: &str, content: &str) -> String {
+        let path = self.0.join(file_name);
+        std::fs::write(&path, content).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    /// Save a player with some experience in Agilité, since rolls update the player file
+    pub fn player(&self) -> Player {
+        let path = self.write(
+            "player.json",
+            r#"{"name": "Asterix", "discord_name": "Obelix", "stats": {"Agilité": 42},
+            "affinities": {"major": [], "minor": []}, "talents": [], "modifiers": {}}"#,
+        );
+        Player::from(&path).unwrap()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}